@@ -0,0 +1,131 @@
+//! Application: a game loop driver on top of the [`World`]
+
+use crate::{
+    sys::{
+        erased::{IntoSystemResult, SystemResult},
+        owned::{BoxSystem, ExclusiveBoxSystem, IntoBoxSystem, IntoExclusiveBoxSystem},
+    },
+    world::res::Resource,
+    World,
+};
+
+/// Ordered list of systems run sequentially
+///
+/// If the [`CommandQueue`](crate::cmd::CommandQueue) resource is set, it's applied after each system with
+/// [`World::apply_commands`], so that the following systems can observe the mutations.
+#[derive(Debug, Default)]
+pub struct Stage {
+    systems: Vec<StageSystem>,
+}
+
+#[derive(Debug)]
+enum StageSystem {
+    Shared(BoxSystem<SystemResult>),
+    Exclusive(ExclusiveBoxSystem<SystemResult>),
+}
+
+impl Stage {
+    pub fn add_system<Params, Ret: IntoSystemResult + 'static>(
+        &mut self,
+        sys: impl IntoBoxSystem<Params, Ret>,
+    ) -> &mut Self {
+        let sys = sys.into_box_system().into_result_system();
+        self.systems.push(StageSystem::Shared(sys));
+        self
+    }
+
+    pub fn add_exclusive_system<Params, Ret: IntoSystemResult + 'static>(
+        &mut self,
+        sys: impl IntoExclusiveBoxSystem<Params, Ret>,
+    ) -> &mut Self {
+        let sys = sys.into_ex_box_system().into_result_system();
+        self.systems.push(StageSystem::Exclusive(sys));
+        self
+    }
+
+    /// Runs the systems in the insertion order. Returns the first error, if any.
+    pub fn run(&mut self, world: &mut World) -> SystemResult {
+        for sys in &mut self.systems {
+            match sys {
+                StageSystem::Shared(sys) => sys.run(world)?,
+                StageSystem::Exclusive(sys) => sys.run_ex(world)?,
+            }
+
+            world.apply_commands();
+        }
+
+        Ok(())
+    }
+}
+
+/// Resource that stops [`App::run`] after the current tick
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AppExit;
+
+/// [`World`] with startup and update [`Stage`]s
+#[derive(Debug, Default)]
+pub struct App {
+    world: World,
+    startup: Stage,
+    update: Stage,
+    is_started: bool,
+}
+
+impl App {
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn insert_resource<T: Resource>(&mut self, res: T) -> &mut Self {
+        self.world.set_res(res);
+        self
+    }
+
+    /// Adds a system run once before the first update
+    pub fn add_startup_system<Params, Ret: IntoSystemResult + 'static>(
+        &mut self,
+        sys: impl IntoBoxSystem<Params, Ret>,
+    ) -> &mut Self {
+        self.startup.add_system(sys);
+        self
+    }
+
+    /// Adds a system run on each update
+    pub fn add_system<Params, Ret: IntoSystemResult + 'static>(
+        &mut self,
+        sys: impl IntoBoxSystem<Params, Ret>,
+    ) -> &mut Self {
+        self.update.add_system(sys);
+        self
+    }
+
+    /// Adds an exclusive system run on each update
+    pub fn add_exclusive_system<Params, Ret: IntoSystemResult + 'static>(
+        &mut self,
+        sys: impl IntoExclusiveBoxSystem<Params, Ret>,
+    ) -> &mut Self {
+        self.update.add_exclusive_system(sys);
+        self
+    }
+
+    /// Runs one tick. The startup systems are run before the first update.
+    pub fn update(&mut self) -> SystemResult {
+        if !self.is_started {
+            self.is_started = true;
+            self.startup.run(&mut self.world)?;
+        }
+        self.update.run(&mut self.world)
+    }
+
+    /// Runs ticks until [`AppExit`] is set or any system returns an error
+    pub fn run(&mut self) -> SystemResult {
+        while !self.world.res.contains::<AppExit>() {
+            self.update()?;
+        }
+        Ok(())
+    }
+}
@@ -33,6 +33,8 @@ pub trait Command: Send + Sync + 'static {
 struct CommandMeta {
     offset: usize,
     func: unsafe fn(value: *mut u8, world: &mut World),
+    /// Drops the command without applying it
+    drop: unsafe fn(value: *mut u8),
 }
 
 /// A queue of [`Command`]s
@@ -75,12 +77,18 @@ impl CommandQueue {
             command.write(world);
         }
 
+        /// SAFE: Same as `write_command`, but the command is dropped instead of being applied.
+        unsafe fn drop_command<T: Command>(command: *mut u8) {
+            drop(command.cast::<T>().read_unaligned());
+        }
+
         let size = std::mem::size_of::<C>();
         let old_len = self.bytes.len();
 
         self.metas.push(CommandMeta {
             offset: old_len,
             func: write_command::<C>,
+            drop: drop_command::<C>,
         });
 
         if size > 0 {
@@ -104,6 +112,31 @@ impl CommandQueue {
         std::mem::forget(command);
     }
 
+    /// Returns the number of queued commands
+    pub fn len(&self) -> usize {
+        self.metas.len()
+    }
+
+    /// Returns true if no command is queued
+    pub fn is_empty(&self) -> bool {
+        self.metas.is_empty()
+    }
+
+    /// Drops the queued [`Command`]s without applying them
+    pub fn clear(&mut self) {
+        // SAFE: In the iteration below, `meta.drop` will safely consume and drop each pushed
+        // command. See `apply`.
+        unsafe { self.bytes.set_len(0) };
+        let byte_ptr = self.byte_ptr();
+
+        for meta in self.metas.drain(..) {
+            // SAFE: The implementation of `drop_command` is safe for the according Command type.
+            unsafe {
+                (meta.drop)(byte_ptr.add(meta.offset));
+            }
+        }
+    }
+
     /// Execute the queued [`Command`]s in the world.
     /// This clears the queue.
     #[inline]
@@ -115,8 +148,19 @@ impl CommandQueue {
         // This operation is so that we can reuse the bytes `Vec<u8>`'s internal storage and prevent
         // unnecessary allocations.
         unsafe { self.bytes.set_len(0) };
+        let byte_ptr = self.byte_ptr();
+
+        for meta in self.metas.drain(..) {
+            // SAFE: The implementation of `write_command` is safe for the according Command type.
+            // The bytes are safely cast to their original type, safely read, and then dropped.
+            unsafe {
+                (meta.func)(byte_ptr.add(meta.offset), world);
+            }
+        }
+    }
 
-        let byte_ptr = if self.bytes.as_mut_ptr().is_null() {
+    fn byte_ptr(&mut self) -> *mut u8 {
+        if self.bytes.as_mut_ptr().is_null() {
             // SAFE: If the vector's buffer pointer is `null` this mean nothing has been pushed to its bytes.
             // This means either that:
             //
@@ -130,18 +174,16 @@ impl CommandQueue {
             unsafe { std::ptr::NonNull::dangling().as_mut() }
         } else {
             self.bytes.as_mut_ptr()
-        };
-
-        for meta in self.metas.drain(..) {
-            // SAFE: The implementation of `write_command` is safe for the according Command type.
-            // The bytes are safely cast to their original type, safely read, and then dropped.
-            unsafe {
-                (meta.func)(byte_ptr.add(meta.offset), world);
-            }
         }
     }
 }
 
+impl Drop for CommandQueue {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
 impl<F> Command for F
 where
     F: FnOnce(&mut World) + Send + Sync + 'static,
@@ -151,9 +193,6 @@ where
     }
 }
 
-    }
-}
-
 /// Despawns an [`Entity`] from the [`World`]
 #[derive(Debug)]
 pub struct Despawn {
@@ -170,6 +209,18 @@ impl Command for Despawn {
     }
 }
 
+/// Spawns an [`Entity`] with a [`ComponentSet`]
+#[derive(Debug)]
+pub struct Spawn<T> {
+    pub comp: T,
+}
+
+impl<T: ComponentSet> Command for Spawn<T> {
+    fn write(self, world: &mut World) {
+        world.spawn(self.comp);
+    }
+}
+
 /// Inserts [`ComponentSet`] to the [`World`]
 #[derive(Debug)]
 pub struct Insert<T> {
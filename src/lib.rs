@@ -37,15 +37,18 @@ macro_rules! run_seq_ex {
 	}};
 }
 
-use std::{any::TypeId, cell::RefCell, fmt, mem};
+use std::{any::TypeId, cell::RefCell, fmt, mem, panic};
+
+use rustc_hash::FxHashMap;
 
 use crate::{
     sys::System,
     world::{
         comp::{self, Comp, CompMut, Component, ComponentPoolMap},
-        ent::{Entity, EntityPool},
-        fetch,
+        ent::{DespawnError, Entity, EntityPool, EntityRef, MapEntities},
+        fetch, hierarchy,
         res::{self, Res, ResMut, Resource, ResourceMap},
+        sparse::Generation,
         ComponentSet, ResourceSet,
     },
 };
@@ -61,6 +64,55 @@ pub struct World {
 unsafe impl Send for World {}
 unsafe impl Sync for World {}
 
+/// In-memory copy of the entities and components of a [`World`]. See [`World::snapshot`].
+#[derive(Debug)]
+pub struct WorldSnapshot {
+    ents: EntityPool,
+    comp: FxHashMap<TypeId, comp::PoolSnapshot>,
+}
+
+impl WorldSnapshot {
+    /// Returns the changes from `self` to `other`. Apply it with [`World::apply_diff`] to a world
+    /// in the state of `self`.
+    ///
+    /// # Panics
+    /// Panics if any component pool is not registered with
+    /// [`register_diff`](World::register_diff).
+    pub fn diff(&self, other: &Self) -> WorldDiff {
+        let comp = other
+            .comp
+            .iter()
+            .map(|(ty, new)| (*ty, new.diff_from(self.comp.get(ty))))
+            .filter(|(_, diff)| !diff.is_empty())
+            .collect();
+
+        WorldDiff {
+            ents: other.ents.clone(),
+            comp,
+        }
+    }
+}
+
+/// Changes between two [`WorldSnapshot`]s. Only changed, added or removed components are recorded
+/// (while the entity pool is copied entirely).
+#[derive(Debug)]
+pub struct WorldDiff {
+    ents: EntityPool,
+    comp: FxHashMap<TypeId, Box<dyn comp::ErasedPoolDiff>>,
+}
+
+impl WorldDiff {
+    /// Returns the changes of component pool of type `T`, if any
+    pub fn get<T: Component + Clone>(&self) -> Option<&comp::PoolDiff<T>> {
+        self.comp.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Returns the number of component pools changed
+    pub fn n_pools(&self) -> usize {
+        self.comp.len()
+    }
+}
+
 /// # Resource API
 impl World {
     /// Sets a resource, a unique instance of type `T`. Returns some old value if it's present.
@@ -68,27 +120,79 @@ impl World {
         self.res.insert(res)
     }
 
+    /// Sets a resource built from the world. Returns some old value if it's present.
+    ///
+    /// If `f` sets a resource of the same type, it's overwritten by the returned value (the last
+    /// writer wins).
+    pub fn set_res_with<T: Resource>(&mut self, f: impl FnOnce(&mut World) -> T) -> Option<T> {
+        let res = f(self);
+        self.set_res(res)
+    }
+
+    /// Replaces a resource with the value made from the old one, if any
+    ///
+    /// If `f` panics, the resource is left absent because the old value is consumed by `f`.
+    pub fn replace_res_with<T: Resource>(&mut self, f: impl FnOnce(Option<T>) -> T) {
+        let old = self.res.remove::<T>();
+        self.res.insert(f(old));
+    }
+
     /// Sets a set of of resources
     pub fn set_res_set<T: ResourceSet>(&mut self, set: T) {
         set.insert(self);
     }
 
+    /// Sets a resource only if it's absent. Returns true if it was inserted.
+    ///
+    /// Useful for plugins providing default resources without overwriting the user's ones.
+    pub fn set_res_if_new<T: Resource>(&mut self, res: T) -> bool {
+        if self.res.contains::<T>() {
+            return false;
+        }
+        self.res.insert(res);
+        true
+    }
+
+    /// Sets the default value of a resource if it's absent and returns a mutable reference to it
+    pub fn init_res<T: Resource + Default>(&mut self) -> &mut T {
+        self.res.entry::<T>().or_insert_with(T::default)
+    }
+
+    /// Sets a resource made by a system if it's absent, and returns a mutable access to it. It's
+    /// useful for resources derived from components, such as spatial indices.
+    ///
+    /// # Panics
+    /// Panics if the system borrows unregistered data or if the system has self confliction.
+    pub fn init_res_with_system<T, Params, S>(&mut self, init: S) -> ResMut<T>
+    where
+        T: Resource,
+        S: System<Params, T>,
+    {
+        if !self.res.contains::<T>() {
+            let res = self.run(init);
+            self.res.insert(res);
+        }
+        self.res_mut::<T>()
+    }
+
     /// Takes out a resource
     pub fn take_res<T: Resource>(&mut self) -> Option<T> {
         self.res.remove()
     }
 
-    /// Takes out a set of resource
-    pub fn take_res_set<T: ResourceSet>(&mut self) {
-        T::take(self);
+    /// Takes out a set of resources. Returns the old values, e.g., `(Option<T0>, Option<T1>)`.
+    pub fn take_res_set<T: ResourceSet>(&mut self) -> T::Taken {
+        T::take(self)
     }
 
     /// Tries to get an immutable access to a resource of type `T`
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn try_res<T: Resource>(&self) -> Result<Res<T>, res::BorrowError> {
         self.res.try_borrow::<T>()
     }
 
     /// Tries to get a mutable access to a resource of type `T`
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn try_res_mut<T: Resource>(&self) -> Result<ResMut<T>, res::BorrowError> {
         self.res.try_borrow_mut::<T>()
     }
@@ -96,6 +200,7 @@ impl World {
     /// Tries to get an immutable access to a resource of type `T`
     /// # Panics
     /// Panics when breaking the aliaslng rules. Panics when the resource is not set.
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn res<T: Resource>(&self) -> Res<T> {
         self.res.try_borrow::<T>().unwrap()
     }
@@ -103,10 +208,28 @@ impl World {
     /// Tries to get a mutable access to a resource of type `T`
     /// # Panics
     /// Panics when breaking the aliaslng rules. Panics when the resource is not set.
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn res_mut<T: Resource>(&self) -> ResMut<T> {
         self.res.try_borrow_mut::<T>().unwrap()
     }
 
+    /// Gets an insert-or-modify access to a resource of type `T`, like `HashMap::entry`
+    pub fn res_entry<T: Resource>(&mut self) -> res::ResEntry<T> {
+        self.res.entry::<T>()
+    }
+
+    /// Returns a clone of a resource without holding the borrow
+    ///
+    /// # Panics
+    /// Panics if the resource is mutably borrowed.
+    pub fn res_cloned<T: Resource + Clone>(&self) -> Option<T> {
+        match self.try_res::<T>() {
+            Ok(res) => Some(T::clone(&res)),
+            Err(res::BorrowError::NotFound(_)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
     /// Runs a procedure that takes `&mut T` and `&mut World` temporarily taking `T` from the world
     pub fn res_scope<T: Resource, Ret>(
         &mut self,
@@ -123,6 +246,11 @@ impl World {
         assert!(self.set_res(res).is_none());
         ret
     }
+
+    /// Visits every resource mutably. Downcast them to access the concrete types.
+    pub fn resources_scope(&mut self, f: impl FnMut(&mut dyn Resource)) {
+        self.res.values_mut().for_each(f);
+    }
 }
 
 /// # Entity / Component API
@@ -132,6 +260,38 @@ impl World {
         self.comp.is_registered::<T>()
     }
 
+    /// Returns an iterator of entities that have component `T`, holding the borrow of the
+    /// component pool. It's empty if the component is not registered.
+    ///
+    /// # Panics
+    /// Panics if the component pool is mutably borrowed.
+    pub fn entities_with<T: Component>(&self) -> impl Iterator<Item = Entity> + '_ {
+        let comp = match self.try_comp::<T>() {
+            Ok(comp) => Some(comp),
+            Err(comp::BorrowError::NotRegistered(_)) => None,
+            Err(err) => panic!("{}", err),
+        };
+
+        let len = comp.as_ref().map_or(0, |comp| comp.entities().len());
+        (0..len).filter_map(move |slot| {
+            // skip the tombstones of a frozen pool
+            let comp = comp.as_ref().filter(|comp| !comp.is_tombstone(slot))?;
+            comp.entity_at(slot)
+        })
+    }
+
+    /// Returns the number of entities that have component `T`, or zero if it's not registered
+    ///
+    /// # Panics
+    /// Panics if the component pool is mutably borrowed.
+    pub fn count<T: Component>(&self) -> usize {
+        match self.try_comp::<T>() {
+            Ok(comp) => comp.len(),
+            Err(comp::BorrowError::NotRegistered(_)) => 0,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
     /// [`is_registered`] by `TypeId`
     ///
     /// [`is_registered`]: Self::is_registered
@@ -144,6 +304,37 @@ impl World {
         self.comp.register::<T>()
     }
 
+    /// Registers a component pool for type `T` with the capacity reserved. Returns true if it was
+    /// already registered.
+    pub fn register_with_capacity<T: Component>(&mut self, cap: usize) -> bool {
+        self.comp.register_with_capacity::<T>(cap)
+    }
+
+    /// Registers a component pool for type `T` if it's not registered yet, and returns a mutable
+    /// access to it. This never panics because it has exclusive access to the world.
+    pub fn register_and_borrow_mut<T: Component>(&mut self) -> CompMut<T> {
+        self.comp.register::<T>();
+        self.comp_mut::<T>()
+    }
+
+    /// Registers a component pool for type `T` along with its entity remapper used by
+    /// [`map_entities`](Self::map_entities)
+    pub fn register_map_entities<T: Component + MapEntities>(&mut self) {
+        self.comp.register_map_entities::<T>();
+    }
+
+    /// Registers a component pool for type `T` along with its cloner used by
+    /// [`snapshot`](Self::snapshot)
+    pub fn register_clone<T: Component + Clone>(&mut self) {
+        self.comp.register_clone::<T>();
+    }
+
+    /// Registers a component pool for type `T` along with its cloner and differ used by
+    /// [`WorldSnapshot::diff`]
+    pub fn register_diff<T: Component + Clone + PartialEq>(&mut self) {
+        self.comp.register_diff::<T>();
+    }
+
     /// Regregister a set of component pools
     pub fn register_set<C: ComponentSet>(&mut self) {
         C::register(&mut self.comp);
@@ -156,6 +347,15 @@ impl World {
         ent
     }
 
+    /// Spawns one [`Entity`] per component set, e.g., from a `Vec<C>`. Returns the entities in the
+    /// same order.
+    pub fn spawn_each<C: ComponentSet>(
+        &mut self,
+        comps: impl IntoIterator<Item = C>,
+    ) -> Vec<Entity> {
+        comps.into_iter().map(|comps| self.spawn(comps)).collect()
+    }
+
     /// Spawns an [`Entity`] with no component
     pub fn spawn_empty(&mut self) -> Entity {
         self.ents.alloc()
@@ -163,31 +363,169 @@ impl World {
 
     /// Reserves an [`Entity`], only requireing `&self`. Make sure to call
     /// [`synchronize`](Self::synchronize) before use.
-    pub fn reserve_atomic(&mut self) -> Entity {
+    pub fn reserve_atomic(&self) -> Entity {
         self.ents.reserve_atomic()
     }
 
+    /// Reserves capacity for at least `n` more entities, such as before spawning a batch of
+    /// entities
+    pub fn reserve_entity_capacity(&mut self, n: usize) {
+        self.ents.reserve(n);
+    }
+
     /// Spawns all the reserved entities
     pub fn synchronize(&mut self) {
         self.ents.synchronize()
     }
 
+    /// Spawns a reserved [`Entity`] with components
+    ///
+    /// This [`synchronize`](Self::synchronize)s all the reserved entities, which are allocated in
+    /// the order of reservation, so other reserved entities are also spawned (without components).
+    pub fn spawn_reserved<C: ComponentSet>(&mut self, reserved: Entity, comps: C) {
+        self.synchronize();
+        debug_assert!(
+            self.contains(reserved),
+            "tried to spawn an entity that is not reserved: {}",
+            reserved
+        );
+        self.insert_set(reserved, comps);
+    }
+
+    /// Reserves an [`Entity`] only requiring `&self`, and returns a procedure that spawns it with
+    /// the components at a sync point.
+    ///
+    /// The procedure [`synchronize`](Self::synchronize)s all the reserved entities, just like
+    /// [`spawn_reserved`](Self::spawn_reserved).
+    pub fn spawn_deferred<C: ComponentSet>(&self, comps: C) -> (Entity, impl FnOnce(&mut World)) {
+        let ent = self.reserve_atomic();
+        (ent, move |world: &mut World| world.spawn_reserved(ent, comps))
+    }
+
     /// Despawns an [`Entity`]. Returns true if it is an existing entity.
     pub fn despawn(&mut self, ent: Entity) -> bool {
-        if !self.ents.contains(ent) {
-            // old entity
-            return false;
-        }
+        self.try_despawn(ent).is_ok()
+    }
+
+    /// Despawns an [`Entity`]. Returns why if it is not an existing entity.
+    pub fn try_despawn(&mut self, ent: Entity) -> Result<(), DespawnError> {
+        self.ents.try_dealloc(ent)?;
 
         self.comp
             .iter_mut()
             .for_each(|comp| comp.erased_remove(ent));
 
-        self.ents.dealloc(ent);
+        Ok(())
+    }
+
+    /// Despawns an [`Entity`], taking out the component set as owned values. Returns `None` if the
+    /// entity is dead or any component is missing, in which case nothing is removed.
+    pub fn despawn_take<C: ComponentSet>(&mut self, ent: Entity) -> Option<C> {
+        if !self.contains(ent) {
+            return None;
+        }
+
+        if !C::type_ids()
+            .iter()
+            .all(|ty| self.comp.contains_raw(*ty, ent))
+        {
+            return None;
+        }
+
+        let set = C::take(ent, self);
+        self.despawn(ent);
+        set
+    }
+
+    /// Despawns every entity that has a component of type `T`. Returns the number of despawned
+    /// entities. It's a no-op if the component is not registered.
+    pub fn despawn_all_with<T: Component>(&mut self) -> usize {
+        if !self.is_registered::<T>() {
+            return 0;
+        }
+
+        let ents = self
+            .comp::<T>()
+            .iter_dense()
+            .map(|(ent, _)| ent)
+            .collect::<Vec<_>>();
+        for ent in &ents {
+            self.despawn(*ent);
+        }
+        ents.len()
+    }
+
+    /// Despawns an [`Entity`] and its descendants in [`Children`](hierarchy::Children), from the
+    /// bottom to the top. Returns true if it is an existing entity.
+    pub fn despawn_recursive(&mut self, root: Entity) -> bool {
+        if !self.contains(root) {
+            return false;
+        }
+
+        hierarchy::detach(self, root);
+        for ent in hierarchy::preorder(self, root).into_iter().rev() {
+            self.despawn(ent);
+        }
 
         true
     }
 
+    /// Registers [`Parent`](hierarchy::Parent) and [`Children`](hierarchy::Children) along with
+    /// their cloners, differs and entity remappers, so that the hierarchy is included in
+    /// [`snapshot`](Self::snapshot), [`WorldSnapshot::diff`] and [`merge`](Self::merge)
+    pub fn register_hierarchy(&mut self) {
+        self.register_diff::<hierarchy::Parent>();
+        self.register_diff::<hierarchy::Children>();
+        self.register_map_entities::<hierarchy::Parent>();
+        self.register_map_entities::<hierarchy::Children>();
+    }
+
+    /// Makes `child` a child of `parent`, keeping [`Parent`](hierarchy::Parent) and
+    /// [`Children`](hierarchy::Children) consistent. The child is removed from the children of
+    /// the previous parent. Returns false if either entity is dead.
+    ///
+    /// The hierarchy component pools are registered if they're not yet, but without cloners. Call
+    /// [`register_hierarchy`](Self::register_hierarchy) to snapshot or merge the hierarchy.
+    pub fn add_child(&mut self, parent: Entity, child: Entity) -> bool {
+        debug_assert_ne!(parent, child, "tried to make an entity its own child");
+        if !(self.contains(parent) && self.contains(child)) {
+            return false;
+        }
+        hierarchy::add_child(self, parent, child);
+        true
+    }
+
+    /// Removes the parent/child relationship. Returns false if they're not parent and child.
+    pub fn remove_child(&mut self, parent: Entity, child: Entity) -> bool {
+        hierarchy::remove_child(self, parent, child)
+    }
+
+    /// Returns an iterator of the descendants of an entity in pre-order
+    ///
+    /// # Panics
+    /// Panics when breaking the aliasing rules.
+    pub fn descendants(&self, root: Entity) -> hierarchy::Descendants {
+        hierarchy::Descendants::new(self, root)
+    }
+
+    /// Returns an iterator of the ancestors of an entity, from the parent to the root
+    ///
+    /// # Panics
+    /// Panics when breaking the aliasing rules.
+    pub fn ancestors(&self, leaf: Entity) -> hierarchy::Ancestors {
+        hierarchy::Ancestors::new(self, leaf)
+    }
+
+    /// Borrows [`Children`](hierarchy::Children) and components of type `T` to iterate through
+    /// the components of the children of each parent
+    ///
+    /// # Panics
+    /// Panics if the component pool of type `T` is not registered. Panics when breaking the
+    /// aliasing rules.
+    pub fn iter_children_components<T: Component>(&self) -> hierarchy::ChildrenComponents<T> {
+        hierarchy::ChildrenComponents::new(self)
+    }
+
     pub fn entities(&mut self) -> &[Entity] {
         self.ents.slice()
     }
@@ -196,12 +534,146 @@ impl World {
         self.ents.contains(ent)
     }
 
+    /// Returns a read-only handle of an entity if it's alive
+    pub fn entity(&self, ent: Entity) -> Option<EntityRef> {
+        if self.contains(ent) {
+            Some(EntityRef::new(self, ent))
+        } else {
+            None
+        }
+    }
+
+    /// Runs a procedure for each entity
+    ///
+    /// [`EntityRef`] borrows component pools lazily on each access, so it panics if the closure
+    /// accesses a component pool that is mutably borrowed outside.
+    pub fn for_each_entity(&self, mut f: impl FnMut(EntityRef)) {
+        for ent in self.ents.iter() {
+            f(EntityRef::new(self, *ent));
+        }
+    }
+
+    /// Returns the current generation of the slot if the entity is stale, i.e., the slot is reused
+    /// by a newer entity. This is handy for debugging dangling entity references.
+    pub fn is_stale(&self, ent: Entity) -> Option<Generation> {
+        self.ents
+            .generation_of(ent.0.raw())
+            .filter(|gen| *gen > ent.generation())
+    }
+
+    /// Remaps entities stored in components, such as after merging a deserialized scene. Only
+    /// component types registered with [`register_map_entities`](Self::register_map_entities)
+    /// are visited.
+    pub fn map_entities(&mut self, mut f: impl FnMut(Entity) -> Entity) {
+        self.comp.map_entities(&mut f);
+    }
+
+    /// Moves the entities, components and resources of another world into this world. Returns
+    /// the map from the entities of `other` to the newly allocated entities.
+    ///
+    /// Components registered with [`register_map_entities`](Self::register_map_entities) in
+    /// either world are remapped. Components referring to entities that are not alive in `other`
+    /// are dropped, since there's nothing to map them to.
+    pub fn merge(
+        &mut self,
+        mut other: World,
+        policy: res::MergePolicy,
+    ) -> FxHashMap<Entity, Entity> {
+        other.synchronize();
+        let map = other
+            .entities()
+            .iter()
+            .map(|&old| (old, self.spawn_empty()))
+            .collect::<FxHashMap<_, _>>();
+        let mut f = |ent: Entity| map.get(&ent).copied();
+
+        self.comp.merge(other.comp, &mut f);
+        self.res.merge(other.res, policy);
+
+        map
+    }
+
+    /// Clones the entities and components, such as for rollback. Resources are not included.
+    ///
+    /// # Panics
+    /// Panics if any component pool is not registered with
+    /// [`register_clone`](Self::register_clone) or if it's mutably borrowed.
+    pub fn snapshot(&self) -> WorldSnapshot {
+        WorldSnapshot {
+            ents: self.ents.clone(),
+            comp: self.comp.snapshot(),
+        }
+    }
+
+    /// Clones the entities and components into a new world. Resources are not cloned.
+    ///
+    /// Returns `Err` listing the component pools that are not registered with
+    /// [`register_clone`](Self::register_clone).
+    ///
+    /// # Panics
+    /// Panics if any component pool is mutably borrowed.
+    pub fn duplicate(&self) -> Result<World, comp::CloneError> {
+        Ok(World {
+            res: ResourceMap::default(),
+            ents: self.ents.clone(),
+            comp: self.comp.try_clone()?,
+        })
+    }
+
+    /// Restores the entities and components from a snapshot. Component pools registered after
+    /// taking the snapshot are cleared.
+    pub fn restore(&mut self, snapshot: WorldSnapshot) {
+        self.comp.restore(snapshot.comp, self.ents.slice());
+        self.ents = snapshot.ents;
+    }
+
+    /// Applies changes between two snapshots. The world has to be in the state of the older
+    /// snapshot.
+    pub fn apply_diff(&mut self, diff: &WorldDiff) {
+        self.comp.apply_diff(&diff.comp);
+        self.ents = diff.ents.clone();
+    }
+
+    /// Visits the components of an entity as trait objects. Only component types registered with
+    /// [`register_map_entities`](Self::register_map_entities) are visited.
+    pub fn visit_components_of(&mut self, ent: Entity, mut f: impl FnMut(&mut dyn MapEntities)) {
+        self.comp.visit_map_entities_of(ent, &mut f);
+    }
+
     /// Tries to get an immutable access to a component pool of type `T`
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn try_comp<T: Component>(&self) -> Result<Comp<T>, comp::BorrowError> {
         self.comp.try_borrow::<T>()
     }
 
-    /// Tries to get a mutable access to a coponent pool of type `Tn`
+    /// Gets an immutable access to the component of an entity. Returns `None` if the entity is
+    /// dead, doesn't have the component or the component pool is not registered.
+    ///
+    /// # Panics
+    /// Panics if the component pool is mutably borrowed.
+    pub fn get<T: Component>(&self, ent: Entity) -> Option<comp::CompRef<T>> {
+        if !self.contains(ent) {
+            return None;
+        }
+        match self.comp.try_borrow::<T>() {
+            Ok(pool) => pool.into_ref(ent),
+            Err(comp::BorrowError::NotRegistered(_)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Gets a mutable reference to the component of an entity. Returns `None` if the entity is
+    /// dead, doesn't have the component or the component pool is not registered. This is safe
+    /// because it has exclusive access.
+    pub fn get_mut<T: Component>(&mut self, ent: Entity) -> Option<&mut T> {
+        if !self.contains(ent) {
+            return None;
+        }
+        self.comp.get_mut::<T>()?.get_mut(ent)
+    }
+
+    /// Tries to get a mutable access to a component pool of type `T`
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn try_comp_mut<T: Component>(&self) -> Result<CompMut<T>, comp::BorrowError> {
         self.comp.try_borrow_mut::<T>()
     }
@@ -209,17 +681,27 @@ impl World {
     /// Tries to get an immutable access to a component pool of type `T`
     /// # Panics
     /// Panics if the component pool is not registered. Panics when breaking the aliaslng rules.
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn comp<T: Component>(&self) -> Comp<T> {
         self.comp.try_borrow::<T>().unwrap()
     }
 
-    /// Tries to get a mutable access to a coponent pool of type `Tn`
+    /// Tries to get a mutable access to a component pool of type `T`
     /// # Panics
     /// Panics if the component pool is not registered. Panics when breaking the aliaslng rules.
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn comp_mut<T: Component>(&self) -> CompMut<T> {
         self.comp.try_borrow_mut::<T>().unwrap()
     }
 
+    /// Gets immutable accesses to multiple component pools at once, e.g.,
+    /// `world.comps::<(A, B, C)>().iter()`
+    /// # Panics
+    /// Panics if any component pool is not registered. Panics when breaking the aliaslng rules.
+    pub fn comps<'w, T: query::CompTuple<'w>>(&'w self) -> query::CompGuard<'w, T> {
+        query::CompGuard::new(self)
+    }
+
     /// Fetches some data. This is type-inference friendly, but prefer explicit alternative such as
     /// [`comp`](Self::comp) or /// [`res`](Self::res) when available.
     pub fn fetch<'w, T: fetch::AutoFetch>(&'w self) -> T
@@ -238,8 +720,21 @@ impl World {
         }
     }
 
-    /// Inserts a set of component to an entity
+    /// Inserts a set of component to an entity. Does nothing if the entity is dead.
+    ///
+    /// # Panics
+    /// Panics in debug build if the set has duplicate component types.
     pub fn insert_set<C: ComponentSet>(&mut self, ent: Entity, set: C) {
+        debug_assert_eq!(
+            C::unique_type_ids().len(),
+            C::type_ids().len(),
+            "component set `{}` has duplicate component types",
+            ::core::any::type_name::<C>()
+        );
+
+        if !self.contains(ent) {
+            return;
+        }
         set.insert(ent, self);
     }
 
@@ -256,10 +751,65 @@ impl World {
     pub fn remove_set<C: ComponentSet>(&mut self, ent: Entity) {
         C::remove(ent, self);
     }
+
+    /// Removes every component from an entity, visiting each component pool once. Unlike
+    /// [`despawn`](Self::despawn), the entity is kept alive. Returns true if it is an existing
+    /// entity.
+    pub fn remove_all_components(&mut self, ent: Entity) -> bool {
+        if !self.contains(ent) {
+            return false;
+        }
+
+        self.comp
+            .iter_mut()
+            .for_each(|comp| comp.erased_remove(ent));
+
+        true
+    }
 }
 
 /// # System API
 impl World {
+    /// Runs a system
+    ///
+    /// # Return value
+    ///
+    /// The borrows of the system are released at the end of the run, so the return value can't
+    /// borrow from the fetched data. It's enforced at compile time:
+    ///
+    /// ```compile_fail
+    /// use toecs::prelude::*;
+    ///
+    /// #[derive(Debug, Component)]
+    /// struct Tag;
+    ///
+    /// fn system(tags: Comp<Tag>) -> Comp<Tag> {
+    ///     tags
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.register::<Tag>();
+    /// let _tags = world.run(system);
+    /// ```
+    ///
+    /// Collect the data into an owned value instead:
+    ///
+    /// ```
+    /// use toecs::prelude::*;
+    ///
+    /// #[derive(Debug, Component)]
+    /// struct Tag;
+    ///
+    /// fn system(tags: Comp<Tag>) -> Vec<Entity> {
+    ///     tags.entities().to_vec()
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.register::<Tag>();
+    /// let e = world.spawn(Tag);
+    /// assert_eq!(world.run(system), [e]);
+    /// ```
+    ///
     /// # Panics
     /// Panics if the system borrows unregistered data or if the system has self confliction.
     pub fn run<Params, Ret, S: System<Params, Ret>>(&self, mut sys: S) -> Ret {
@@ -270,6 +820,68 @@ impl World {
         unsafe { sys.run(self) }
     }
 
+    /// Runs a system after checking that every data it borrows is present
+    ///
+    /// Returns `Err` instead of panicking if the system borrows unregistered components, unset
+    /// resources or has self confliction. It still panics if the data is already borrowed.
+    pub fn try_run<Params, Ret, S: System<Params, Ret>>(
+        &self,
+        mut sys: S,
+    ) -> Result<Ret, sys::RunError> {
+        let accesses = sys.accesses();
+        if accesses.self_conflict() {
+            return Err(sys::RunError::SelfConflict(accesses));
+        }
+
+        for access in accesses.as_slice() {
+            match *access {
+                fetch::Access::Res(ty) | fetch::Access::ResMut(ty) if !self.res.contains_raw(ty) => {
+                    return Err(sys::RunError::ResourceNotFound(ty));
+                }
+                fetch::Access::Comp(ty) | fetch::Access::CompMut(ty)
+                    if !self.is_registered_raw(ty) =>
+                {
+                    return Err(sys::RunError::ComponentNotRegistered(ty));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(unsafe { sys.run(self) })
+    }
+
+    /// Runs a system only if every data it borrows is present. Returns `None` if it borrows
+    /// unregistered components or unset resources.
+    ///
+    /// # Panics
+    /// Panics if the system has self confliction or when breaking the aliasing rules.
+    pub fn run_if_registered<Params, Ret, S: System<Params, Ret>>(&self, sys: S) -> Option<Ret> {
+        match self.try_run(sys) {
+            Ok(ret) => Some(ret),
+            Err(sys::RunError::SelfConflict(_)) => panic!("The system has self confliction!"),
+            Err(_) => None,
+        }
+    }
+
+    /// Runs the systems in a [`Stage`](app::Stage) sequentially
+    pub fn run_stage(&mut self, stage: &mut app::Stage) -> sys::erased::SystemResult {
+        stage.run(self)
+    }
+
+    /// Runs exclusive systems in order, stopping at the first error. This is the runtime version
+    /// of [`run_seq_ex!`]. Make the systems with
+    /// [`into_ex_box_system`](sys::owned::IntoExclusiveBoxSystem::into_ex_box_system) and
+    /// [`into_result_system`](sys::owned::ExclusiveBoxSystem::into_result_system).
+    pub fn run_seq(
+        &mut self,
+        systems: &mut [sys::owned::ExclusiveBoxSystem<sys::erased::SystemResult>],
+    ) -> sys::erased::SystemResult {
+        for sys in systems {
+            sys.run_ex(self)?;
+        }
+        Ok(())
+    }
+
     /// Runs a procedure with exclusive access to the [`World`]
     // TODO: allow ordinary system
     pub fn run_ex<S, Params, Ret>(&mut self, mut sys: S) -> Ret
@@ -279,6 +891,19 @@ impl World {
         unsafe { sys.run_ex(self) }
     }
 
+    /// Runs an exclusive system that returns `()` or [`SystemResult`](sys::erased::SystemResult).
+    /// It's a single-system version of [`run_seq_ex!`], so the result can be propagated with `?`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system borrows unregistered data or if the system has self confliction.
+    pub fn run_ex_result<S, Params, Ret>(&mut self, mut sys: S) -> sys::erased::SystemResult
+    where
+        S: sys::erased::ExclusiveResultSystem<Params, Ret>,
+    {
+        unsafe { sys.run_as_result_ex(self) }
+    }
+
     /// Run a system with user argumewnt
     ///
     /// # Example
@@ -313,6 +938,23 @@ impl World {
         unsafe { sys.run_arg(data, self) }
     }
 
+    /// Runs a system with a resource of type `R` as user argument. The resource is taken out of
+    /// the world during the run and put back after that, even if the system panics.
+    ///
+    /// # Panics
+    /// Panics if the resource is not set, if the system borrows unregistered data or if the system
+    /// has self confliction.
+    pub fn run_arg_res<R, Params, Ret, S>(&mut self, sys: S) -> Ret
+    where
+        R: Resource,
+        S: for<'r> sys::ArgSystem<&'r mut R, Params, Ret>,
+    {
+        let ret = self.res_scope(|res: &mut R, world| {
+            panic::catch_unwind(panic::AssertUnwindSafe(|| world.run_arg(sys, res)))
+        });
+        ret.unwrap_or_else(|err| panic::resume_unwind(err))
+    }
+
     /// Run an exclusive system with user argumewnt
     ///
     /// # Example
@@ -329,10 +971,126 @@ impl World {
     ) -> Ret {
         unsafe { sys.run_arg_ex(data, self) }
     }
+
+    /// Returns the world's [`CommandQueue`](cmd::CommandQueue), which is stored as a resource so
+    /// that systems can push commands to it via `ResMut<CommandQueue>`
+    pub fn commands(&mut self) -> &mut cmd::CommandQueue {
+        self.res.get_or_insert_with(cmd::CommandQueue::default)
+    }
+
+    /// Applies the commands in the world's [`CommandQueue`](cmd::CommandQueue). Commands pushed
+    /// to the queue while applying are also applied.
+    pub fn apply_commands(&mut self) {
+        loop {
+            let mut cmds = match self.take_res::<cmd::CommandQueue>() {
+                Some(cmds) if !cmds.is_empty() => cmds,
+                Some(cmds) => {
+                    self.set_res(cmds);
+                    return;
+                }
+                None => return,
+            };
+
+            cmds.apply(self);
+
+            // keep the new queue if any command pushed to it, otherwise reuse the buffer
+            self.res.entry::<cmd::CommandQueue>().or_insert(cmds);
+        }
+    }
+
+    /// Splits the component pool of type `T` into chunks of `chunk` components and runs `f` on
+    /// them in parallel. It's for per-entity work that only mutates one component pool.
+    ///
+    /// While the pool is frozen, the chunks include the tombstones (see
+    /// [`ComponentPool::freeze`](comp::ComponentPool::freeze)).
+    ///
+    /// # Panics
+    /// Panics if the component is not registered or if `chunk` is zero.
+    #[cfg(feature = "rayon")]
+    pub fn run_par_chunks<T, F>(&mut self, chunk: usize, f: F)
+    where
+        T: Component,
+        F: Fn(&mut [T]) + Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        let mut comps = self.comp_mut::<T>();
+        comps.as_mut_slice().par_chunks_mut(chunk).for_each(f);
+    }
 }
 
 /// # Misc
 impl World {
+    /// Groups entities by the exact set of components they have, like archetypes. Returns pairs
+    /// of sorted component types and the number of entities, in descending order of the count.
+    ///
+    /// This is O(entities × component pools) and is intended for debugging.
+    ///
+    /// # Panics
+    /// Panics if any component pool is mutably borrowed.
+    pub fn archetype_summary(&self) -> Vec<(Vec<TypeId>, usize)> {
+        self.comp.archetype_summary(self.ents.slice())
+    }
+
+    /// Returns type names and heap memory usages of the component pools in bytes, in descending
+    /// order of the usage
+    ///
+    /// # Panics
+    /// Panics if any component pool is mutably borrowed.
+    pub fn memory_report(&self) -> Vec<(&'static str, usize)> {
+        let mut report = self.comp.memory_report();
+        report.sort_by(|(t1, n1), (t2, n2)| n2.cmp(n1).then_with(|| t1.cmp(t2)));
+        report
+    }
+
+    /// Returns the accesses of a system without running it or checking its confliction. See also
+    /// [`report_accesses`](Self::report_accesses).
+    pub fn system_accesses<Params, Ret, S: System<Params, Ret>>(sys: &S) -> fetch::AccessSet {
+        sys.accesses()
+    }
+
+    /// Returns human-readable names of the accesses such as `CompMut<T>`. Types of unset
+    /// resources or unregistered components are shown with their `TypeId`s. This is safe because
+    /// it has exclusive access.
+    pub fn report_accesses(&mut self, accesses: &fetch::AccessSet) -> Vec<String> {
+        accesses
+            .as_slice()
+            .iter()
+            .map(|access| self.access_name(*access))
+            .collect()
+    }
+
+    /// Panics if the accesses have self confliction, naming the conflicting types
+    pub(crate) fn assert_no_self_conflict(&mut self, accesses: &fetch::AccessSet) {
+        let conflicts = fetch::AccessSet::new(accesses.self_conflicting_types());
+        if conflicts.as_slice().is_empty() {
+            return;
+        }
+
+        let names = self.report_accesses(&conflicts);
+        panic!("The system has self confliction: {}", names.join(", "));
+    }
+
+    fn access_name(&mut self, access: fetch::Access) -> String {
+        use fetch::Access;
+
+        // the type name is unknown if the data is not present
+        let (kind, name) = match access {
+            Access::Res(ty) => ("Res", self.res.type_name_raw(ty)),
+            Access::ResMut(ty) => ("ResMut", self.res.type_name_raw(ty)),
+            Access::Comp(ty) => ("Comp", self.comp.type_name_raw(ty)),
+            Access::CompMut(ty) => ("CompMut", self.comp.type_name_raw(ty)),
+            Access::Entities => return "&EntityPool".to_string(),
+            Access::EntitiesMut => return "&mut EntityPool".to_string(),
+            Access::World => return "&World".to_string(),
+        };
+
+        match name {
+            Some(name) => format!("{}<{}>", kind, name),
+            None => format!("{:?}", access),
+        }
+    }
+
     /// Returns a debug display. This is safe because it has exclusive access.
     pub fn display(&mut self) -> WorldDisplay {
         let mut world = World::default();
@@ -342,6 +1100,66 @@ impl World {
             original_world: self,
         }
     }
+
+    /// Returns a JSON-like dump of the resources, entities and component pools. Each value is
+    /// printed with its `Debug` implementation.
+    pub fn dump_json(&mut self) -> String {
+        let mut res = self.res.iter_debug().collect::<Vec<_>>();
+        res.sort_by_key(|(name, _)| *name);
+
+        let mut comp = self.comp.iter_debug().collect::<Vec<_>>();
+        comp.sort_by_key(|(name, _)| *name);
+
+        let mut out = String::new();
+        out.push_str("{\n  \"res\": ");
+        self::push_json_map(&mut out, &res);
+        out.push_str(",\n  \"ents\": ");
+        self::push_json_str(&mut out, &format!("{:?}", self.ents.slice()));
+        out.push_str(",\n  \"comp\": ");
+        self::push_json_map(&mut out, &comp);
+        out.push_str("\n}\n");
+        out
+    }
+}
+
+fn push_json_map(out: &mut String, entries: &[(&'static str, &dyn fmt::Debug)]) {
+    if entries.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+
+    out.push_str("{\n");
+    for (i, (name, value)) in entries.iter().enumerate() {
+        out.push_str("    ");
+        self::push_json_str(out, name);
+        out.push_str(": ");
+        self::push_json_str(out, &format!("{:?}", value));
+        if i + 1 < entries.len() {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str("  }");
+}
+
+fn push_json_str(out: &mut String, s: &str) {
+    use fmt::Write;
+
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
 }
 
 /// See [`World::display`]
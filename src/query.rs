@@ -2,19 +2,58 @@
 
 use std::{marker::PhantomData, mem::MaybeUninit};
 
-use crate::world::{
-    comp::{Comp, CompMut, Component, ComponentPool},
-    ent::Entity,
-    sparse::DenseIndex,
+use crate::{
+    world::{
+        comp::{self, Comp, CompMut, Component, ComponentPool},
+        ent::Entity,
+        sparse::DenseIndex,
+    },
+    World,
 };
 
 /// Iterator constructing API
 pub trait Iter<'a> {
     /// Concrete iterator type returned by `iter`
     type I;
+    /// Concrete iterator type returned by `iter_entities`
+    type E;
     /// Returns an iterator of components. Chain `.entities()` like `.enumerate()` if [`Entity`] is
     /// needed too.
     fn iter(self) -> Self::I;
+    /// Returns an iterator of the given entities and their components. Entities that don't have
+    /// all of the components are skipped.
+    ///
+    /// # Panics
+    /// Panics if `ents` has duplicates and any of the views is mutable.
+    fn iter_entities(self, ents: &'a [Entity]) -> Self::E;
+
+    /// Returns the item if there's exactly one match
+    fn try_single(self) -> Option<<Self::I as Iterator>::Item>
+    where
+        Self: Sized,
+        Self::I: Iterator,
+    {
+        let mut iter = self.iter();
+        let item = iter.next()?;
+        match iter.next() {
+            Some(_) => None,
+            None => Some(item),
+        }
+    }
+
+    /// Returns the item if there's exactly one match
+    /// # Panics
+    /// Panics if there are zero or more than one matches.
+    fn single(self) -> <Self::I as Iterator>::Item
+    where
+        Self: Sized,
+        Self::I: Iterator,
+    {
+        let mut iter = self.iter();
+        let item = iter.next().expect("query has no match");
+        assert!(iter.next().is_none(), "query has more than one match");
+        item
+    }
 }
 
 /// View to a component pool (a sparse set)
@@ -31,8 +70,15 @@ type ViewItem<'a, V> = <<V as View<'a>>::Binding as AnyBinding>::Item;
 /// Slice that can be indexed by `usize` or [`Entity`]
 pub trait AnyBinding {
     type Item;
+    /// True if the items are mutable references, which must not be yielded twice
+    const MUTABLE: bool = false;
     fn get(&mut self, ent: Entity) -> Option<Self::Item>;
     unsafe fn get_by_slot_unchecked(&mut self, slot: usize) -> Self::Item;
+
+    /// Returns true if the dense slot is a tombstone of a frozen pool
+    fn is_tombstone(&self, _slot: usize, _ent: Entity) -> bool {
+        false
+    }
 }
 
 /// Implementation of [`AnyBinding`]
@@ -40,40 +86,49 @@ pub trait AnyBinding {
 pub struct Binding<'a, Slice> {
     to_dense: &'a [Option<DenseIndex>],
     data: Slice,
+    /// If true, the dense slots can be tombstones
+    frozen: bool,
+}
+
+impl<'a, Slice> Binding<'a, Slice> {
+    fn is_tombstone_slot(&self, slot: usize, ent: Entity) -> bool {
+        self.frozen && comp::is_tombstone(self.to_dense, ent, slot)
+    }
 }
 
 impl<'a, T> AnyBinding for Binding<'a, &'a [T]> {
     type Item = &'a T;
 
     fn get(&mut self, ent: Entity) -> Option<Self::Item> {
-        self.to_dense.get(ent.0.to_usize()).and_then(|opt| {
-            if let Some(dense) = opt {
+        self.to_dense.get(ent.0.to_usize()).and_then(|opt| match opt {
+            Some(dense) if dense.generation() == ent.generation() => {
                 Some(&self.data[dense.to_usize()])
-            } else {
-                None
             }
+            _ => None,
         })
     }
 
     unsafe fn get_by_slot_unchecked(&mut self, slot: usize) -> Self::Item {
         self.data.get_unchecked(slot)
     }
+
+    fn is_tombstone(&self, slot: usize, ent: Entity) -> bool {
+        self.is_tombstone_slot(slot, ent)
+    }
 }
 
 /// Dark impl
 impl<'a, T> AnyBinding for Binding<'a, &'a mut [T]> {
     type Item = &'a mut T;
+    const MUTABLE: bool = true;
 
     fn get(&mut self, ent: Entity) -> Option<Self::Item> {
-        self.to_dense.get(ent.0.to_usize()).and_then(|opt| {
-            if let Some(dense) = opt {
-                unsafe {
-                    let ptr = self.data.as_mut_ptr().add(dense.to_usize());
-                    Some(&mut *ptr)
-                }
-            } else {
-                None
-            }
+        self.to_dense.get(ent.0.to_usize()).and_then(|opt| match opt {
+            Some(dense) if dense.generation() == ent.generation() => unsafe {
+                let ptr = self.data.as_mut_ptr().add(dense.to_usize());
+                Some(&mut *ptr)
+            },
+            _ => None,
         })
     }
 
@@ -81,6 +136,10 @@ impl<'a, T> AnyBinding for Binding<'a, &'a mut [T]> {
         let ptr = self.data.as_mut_ptr().add(slot);
         &mut *ptr
     }
+
+    fn is_tombstone(&self, slot: usize, ent: Entity) -> bool {
+        self.is_tombstone_slot(slot, ent)
+    }
 }
 
 // `View` impls
@@ -88,40 +147,80 @@ impl<'a, T> AnyBinding for Binding<'a, &'a mut [T]> {
 unsafe impl<'a, T: Component> View<'a> for &'a ComponentPool<T> {
     type Binding = Binding<'a, &'a [T]>;
     fn into_parts(self) -> (&'a [Entity], Self::Binding) {
+        let frozen = self.is_frozen();
         let (to_dense, ents, data) = self.parts();
-        (ents, Binding { to_dense, data })
+        (
+            ents,
+            Binding {
+                to_dense,
+                data,
+                frozen,
+            },
+        )
     }
 }
 
 unsafe impl<'a, T: Component> View<'a> for &'a mut ComponentPool<T> {
     type Binding = Binding<'a, &'a mut [T]>;
     fn into_parts(self) -> (&'a [Entity], Self::Binding) {
+        let frozen = self.is_frozen();
         let (to_dense, ents, data) = self.parts_mut();
-        (ents, Binding { to_dense, data })
+        (
+            ents,
+            Binding {
+                to_dense,
+                data,
+                frozen,
+            },
+        )
     }
 }
 
 unsafe impl<'a, T: Component> View<'a> for &'a Comp<'_, T> {
     type Binding = Binding<'a, &'a [T]>;
     fn into_parts(self) -> (&'a [Entity], Self::Binding) {
+        let frozen = self.is_frozen();
         let (to_dense, ents, data) = self.deref().parts();
-        (ents, Binding { to_dense, data })
+        (
+            ents,
+            Binding {
+                to_dense,
+                data,
+                frozen,
+            },
+        )
     }
 }
 
 unsafe impl<'a, T: Component> View<'a> for &'a CompMut<'_, T> {
     type Binding = Binding<'a, &'a [T]>;
     fn into_parts(self) -> (&'a [Entity], Self::Binding) {
+        let frozen = self.is_frozen();
         let (to_dense, ents, data) = self.deref().parts();
-        (ents, Binding { to_dense, data })
+        (
+            ents,
+            Binding {
+                to_dense,
+                data,
+                frozen,
+            },
+        )
     }
 }
 
 unsafe impl<'a, T: Component> View<'a> for &'a mut CompMut<'_, T> {
     type Binding = Binding<'a, &'a mut [T]>;
     fn into_parts(self) -> (&'a [Entity], Self::Binding) {
+        let frozen = self.is_frozen();
         let (to_dense, ents, data) = self.deref_mut().parts_mut();
-        (ents, Binding { to_dense, data })
+        (
+            ents,
+            Binding {
+                to_dense,
+                data,
+                frozen,
+            },
+        )
     }
 }
 
@@ -157,6 +256,12 @@ pub(crate) struct SingleIterData<'a, V: View<'a>> {
     bindings: V::Binding,
 }
 
+impl<'a, V: View<'a>> SingleIterData<'a, V> {
+    fn is_tombstone(&self, slot: usize) -> bool {
+        self.bindings.is_tombstone(slot, self.ents[slot])
+    }
+}
+
 impl<'a, V> Iterator for SingleIter<'a, V>
 where
     V: View<'a>,
@@ -164,13 +269,18 @@ where
     type Item = ViewItem<'a, V>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.data.ents.len() {
+        while self.index < self.data.ents.len() {
             let index = self.index;
             self.index += 1;
-            unsafe { Some(self.data.bindings.get_by_slot_unchecked(index)) }
-        } else {
-            None
+            if self.data.is_tombstone(index) {
+                continue;
+            }
+            unsafe {
+                return Some(self.data.bindings.get_by_slot_unchecked(index));
+            }
         }
+
+        None
     }
 }
 
@@ -181,18 +291,21 @@ where
     type Item = (Entity, ViewItem<'a, V>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.data.ents.len() {
+        while self.index < self.data.ents.len() {
             let index = self.index;
             self.index += 1;
+            if self.data.is_tombstone(index) {
+                continue;
+            }
             unsafe {
-                Some((
+                return Some((
                     self.data.ents[index].clone(),
                     self.data.bindings.get_by_slot_unchecked(index),
-                ))
+                ));
             }
-        } else {
-            None
         }
+
+        None
     }
 }
 
@@ -200,6 +313,8 @@ where
 
 impl<'a, V: View<'a>> Iter<'a> for V {
     type I = SingleIter<'a, Self>;
+    type E = EntitiesIter<'a, V::Binding, Self>;
+
     fn iter(self) -> Self::I {
         let (ents, bindings) = self.into_parts();
         SingleIter {
@@ -207,6 +322,67 @@ impl<'a, V: View<'a>> Iter<'a> for V {
             index: 0,
         }
     }
+
+    fn iter_entities(self, ents: &'a [Entity]) -> Self::E {
+        let (_, bindings) = self.into_parts();
+        EntitiesIter::new(ents, bindings, V::Binding::MUTABLE)
+    }
+}
+
+impl<'a, V> Iterator for EntitiesIter<'a, V::Binding, V>
+where
+    V: View<'a>,
+{
+    type Item = (Entity, ViewItem<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.ents.len() {
+            let ent = self.ents[self.index];
+            self.index += 1;
+
+            if let Some(item) = self.bindings.get(ent) {
+                return Some((ent, item));
+            }
+        }
+
+        None
+    }
+}
+
+// Entity-driven iterators
+
+/// Iterator of given entities and items yielded by [`View`] s. See [`Iter::iter_entities`].
+///
+/// This is slow because of the sparse-to-dense map indirection.
+pub struct EntitiesIter<'a, Bindings, Views> {
+    ents: &'a [Entity],
+    index: usize,
+    bindings: Bindings,
+    _ty: PhantomData<Views>,
+}
+
+impl<'a, Bindings, Views> EntitiesIter<'a, Bindings, Views> {
+    fn new(ents: &'a [Entity], bindings: Bindings, mutable: bool) -> Self {
+        // mutable views must not yield the same item twice
+        if mutable {
+            let mut ids = ents
+                .iter()
+                .map(|e| (e.0.to_usize(), e.generation()))
+                .collect::<Vec<_>>();
+            ids.sort_unstable();
+            assert!(
+                ids.windows(2).all(|w| w[0] != w[1]),
+                "duplicate entities given to `iter_entities`"
+            );
+        }
+
+        Self {
+            ents,
+            index: 0,
+            bindings,
+            _ty: PhantomData,
+        }
+    }
 }
 
 /// Multi-view getter functions
@@ -259,6 +435,14 @@ macro_rules! impl_sparse_iterator {
             $($view: View<'a>,)+
         {
             type I = SparseIter<'a, ($($view::Binding),+), ($($view),+), $n>;
+            type E = EntitiesIter<'a, ($($view::Binding),+), ($($view),+)>;
+
+            fn iter_entities(self, ents: &'a [Entity]) -> Self::E {
+                let bindings = ($(
+                    self.$i_view.into_parts().1,
+                )+);
+                EntitiesIter::new(ents, bindings, $($view::Binding::MUTABLE)||+)
+            }
 
             fn iter(self) -> Self::I {
                 unsafe {
@@ -329,6 +513,26 @@ macro_rules! impl_sparse_iterator {
             }
         }
 
+        impl<'a, $($view),+> Iterator for EntitiesIter<'a, ($($view::Binding),+), ($($view),+)>
+        where
+            $($view: View<'a>,)+
+        {
+            type Item = (Entity, ($(ViewItem<'a, $view>),+));
+
+            fn next(&mut self) -> Option<Self::Item> {
+                while self.index < self.ents.len() {
+                    let ent = self.ents[self.index];
+                    self.index += 1;
+
+                    if let Some(items) = self.bindings.get(ent) {
+                        return Some((ent, items));
+                    }
+                }
+
+                None
+            }
+        }
+
         impl<'a, $($view),+> Iterator for SparseIterWithEntities<'a, ($($view::Binding),+), ($($view),+), $n>
         where
             $($view: View<'a>,)+
@@ -399,3 +603,59 @@ recursive_indexed_const_generics!(
         (0, C0),
     ]
 );
+
+/// Tuple of component types that can be borrowed at once. See [`World::comps`].
+pub trait CompTuple<'w> {
+    /// Tuple of [`Comp`] guards
+    type Guards;
+    /// Borrows all the component pools
+    ///
+    /// # Panics
+    /// Panics if any component pool is not registered or is mutably borrowed.
+    fn borrow(world: &'w World) -> Self::Guards;
+}
+
+/// Immutable accesses to multiple component pools. See [`World::comps`].
+pub struct CompGuard<'w, T: CompTuple<'w>> {
+    guards: T::Guards,
+}
+
+impl<'w, T: CompTuple<'w>> CompGuard<'w, T> {
+    pub(crate) fn new(world: &'w World) -> Self {
+        Self {
+            guards: T::borrow(world),
+        }
+    }
+
+    /// Returns the tuple of [`Comp`] guards
+    pub fn guards(&self) -> &T::Guards {
+        &self.guards
+    }
+}
+
+macro_rules! impl_comp_tuple {
+    ($($i:tt, $c:ident),+ $(,)?) => {
+        impl<'w, $($c: Component),+> CompTuple<'w> for ($($c,)+) {
+            type Guards = ($(Comp<'w, $c>,)+);
+
+            fn borrow(world: &'w World) -> Self::Guards {
+                ($(world.comp::<$c>(),)+)
+            }
+        }
+
+        impl<'w, $($c: Component),+> CompGuard<'w, ($($c,)+)> {
+            /// Returns an iterator of the components
+            pub fn iter<'a>(&'a self) -> <($(&'a Comp<'w, $c>,)+) as Iter<'a>>::I {
+                ($(&self.guards.$i,)+).iter()
+            }
+        }
+    };
+}
+
+impl_comp_tuple!(0, C0, 1, C1);
+impl_comp_tuple!(0, C0, 1, C1, 2, C2);
+impl_comp_tuple!(0, C0, 1, C1, 2, C2, 3, C3);
+impl_comp_tuple!(0, C0, 1, C1, 2, C2, 3, C3, 4, C4);
+impl_comp_tuple!(0, C0, 1, C1, 2, C2, 3, C3, 4, C4, 5, C5);
+impl_comp_tuple!(0, C0, 1, C1, 2, C2, 3, C3, 4, C4, 5, C5, 6, C6);
+impl_comp_tuple!(0, C0, 1, C1, 2, C2, 3, C3, 4, C4, 5, C5, 6, C6, 7, C7);
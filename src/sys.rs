@@ -3,11 +3,35 @@
 pub mod erased;
 pub mod owned;
 
+use std::any::TypeId;
+
+use thiserror::Error;
+
 use crate::{
     world::fetch::{AccessSet, Fetch, FetchItem, AutoFetchImpl, AutoFetch},
     World,
 };
 
+/// Error returned by [`World::try_run`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RunError {
+    #[error("the system borrows resource `{0:?}` that is not set")]
+    ResourceNotFound(TypeId),
+    #[error("the system borrows component `{0:?}` that is not registered")]
+    ComponentNotRegistered(TypeId),
+    #[error("the system has self confliction: {0:?}")]
+    SelfConflict(AccessSet),
+}
+
+/// Boxes a system so that systems of different types can be stored in one collection. This is a
+/// shorthand of [`IntoBoxSystem::into_box_system`](owned::IntoBoxSystem::into_box_system).
+pub fn into_box<Params, Ret, S>(sys: S) -> owned::BoxSystem<Ret>
+where
+    S: owned::IntoBoxSystem<Params, Ret>,
+{
+    sys.into_box_system()
+}
+
 /// Procedure that borrows some set of data from the `World` to run
 pub unsafe trait System<Params, Ret> {
     /// # Panics
@@ -141,6 +165,20 @@ recursive!(
     P0,
 );
 
+/// Every `FnMut() -> Ret` is a [`System`] that borrows nothing
+unsafe impl<Ret, F> System<(), Ret> for F
+where
+    F: FnMut() -> Ret,
+{
+    unsafe fn run(&mut self, _w: &World) -> Ret {
+        self()
+    }
+
+    fn accesses(&self) -> AccessSet {
+        AccessSet::default()
+    }
+}
+
 /// Upcast of [`System`] s and function that takes `&mut World`
 pub unsafe trait ExclusiveSystem<Params, Ret> {
     unsafe fn run_ex(&mut self, w: &mut World) -> Ret;
@@ -163,6 +201,7 @@ where
     Params: AutoFetch,
 {
     unsafe fn run_ex(&mut self, w: &mut World) -> Ret {
+        w.assert_no_self_conflict(&self.accesses());
         self.run(w)
     }
 }
@@ -189,6 +228,7 @@ where
     Params: AutoFetch,
 {
     unsafe fn run_arg_ex(&mut self, data: Data, w: &mut World) -> Ret {
+        w.assert_no_self_conflict(&self.accesses());
         self.run_arg(data, w)
     }
 }
@@ -1,9 +1,12 @@
 //! Owned, boxed systems
 
-use std::fmt;
+use std::{any, fmt};
 
 use crate::{
-    sys::{AccessSet, ArgSystem, ExclusiveArgSystem, ExclusiveSystem, System},
+    sys::{
+        erased::{IntoSystemResult, SystemResult},
+        AccessSet, ArgSystem, ExclusiveArgSystem, ExclusiveSystem, System,
+    },
     world::fetch::AutoFetch,
     World,
 };
@@ -12,11 +15,12 @@ use crate::{
 pub struct BoxSystem<Ret> {
     f: Box<dyn for<'w> FnMut(&'w World) -> Ret>,
     accesses: AccessSet,
+    name: &'static str,
 }
 
 impl<Ret> fmt::Debug for BoxSystem<Ret> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "BoxSystem")
+        write!(f, "BoxSystem({})", self.name)
     }
 }
 
@@ -28,6 +32,24 @@ impl<Ret> BoxSystem<Ret> {
     pub fn accesses(&self) -> &AccessSet {
         &self.accesses
     }
+
+    /// Returns the type name of the original system for debugging
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<Ret: IntoSystemResult + 'static> BoxSystem<Ret> {
+    /// Converts the return type into [`SystemResult`]
+    pub fn into_result_system(mut self) -> BoxSystem<SystemResult> {
+        let accesses = self.accesses.clone();
+        let name = self.name;
+        BoxSystem {
+            f: Box::new(move |world: &World| self.run(world).into_result()),
+            accesses,
+            name,
+        }
+    }
 }
 
 /// Owned exclusive system
@@ -51,6 +73,15 @@ impl<Ret> ExclusiveBoxSystem<Ret> {
     }
 }
 
+impl<Ret: IntoSystemResult + 'static> ExclusiveBoxSystem<Ret> {
+    /// Converts the return type into [`SystemResult`]
+    pub fn into_result_system(mut self) -> ExclusiveBoxSystem<SystemResult> {
+        ExclusiveBoxSystem {
+            f: Box::new(move |world: &mut World| self.run_ex(world).into_result()),
+        }
+    }
+}
+
 pub trait IntoExclusiveBoxSystem<Params, Ret> {
     fn into_ex_box_system(self) -> ExclusiveBoxSystem<Ret>;
 }
@@ -119,6 +150,7 @@ macro_rules! impl_into_system {
                 BoxSystem {
                     f,
                     accesses,
+                    name: any::type_name::<S>(),
                 }
             }
         }
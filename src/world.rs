@@ -3,9 +3,11 @@
 #[cfg(test)]
 mod tests;
 
+pub mod borrow;
 pub mod fetch;
 pub mod comp;
 pub mod ent;
+pub mod hierarchy;
 pub mod res;
 pub mod sparse;
 
@@ -30,8 +32,20 @@ pub trait ComponentSet: Send + Sync + 'static {
     fn insert(self, ent: Entity, world: &mut World);
     /// Removes the set of components from an entity
     fn remove(ent: Entity, world: &mut World);
+    /// Removes the set of components from an entity and returns them. Returns `None` if any
+    /// component is missing, but then the other components are still removed.
+    fn take(ent: Entity, world: &mut World) -> Option<Self>
+    where
+        Self: Sized;
     /// Enumerates the component types in this set
     fn type_ids() -> Box<[TypeId]>;
+    /// Enumerates the component types in this set, sorted and without duplicates
+    fn unique_type_ids() -> Box<[TypeId]> {
+        let mut ids = Self::type_ids().into_vec();
+        ids.sort();
+        ids.dedup();
+        ids.into_boxed_slice()
+    }
 }
 
 impl<T: Component> ComponentSet for T {
@@ -47,6 +61,10 @@ impl<T: Component> ComponentSet for T {
         world.remove::<Self>(ent);
     }
 
+    fn take(ent: Entity, world: &mut World) -> Option<Self> {
+        world.remove::<Self>(ent)
+    }
+
     fn type_ids() -> Box<[TypeId]> {
         Box::new([TypeId::of::<T>()])
     }
@@ -77,6 +95,15 @@ macro_rules! impl_component_set {
                 )+
             }
 
+            fn take(ent: Entity, world: &mut World) -> Option<Self> {
+                let taken = ($(
+                    $xs::take(ent, world),
+                )+);
+                Some(($(
+                    taken.$i?,
+                )+))
+            }
+
             fn type_ids() -> Box<[TypeId]> {
                 let mut ids = Vec::new();
                 $(
@@ -138,10 +165,12 @@ recursive_indexed!(
 
 /// Tuple of resources
 pub trait ResourceSet {
+    /// Resources taken out of the world, e.g., `(Option<T0>, Option<T1>)`
+    type Taken;
     /// Inserts the set of resources to the world
     fn insert(self, world: &mut World);
     /// Remove the set of resources from the world
-    fn take(world: &mut World);
+    fn take(world: &mut World) -> Self::Taken;
 }
 
 macro_rules! impl_resource_set {
@@ -150,16 +179,18 @@ macro_rules! impl_resource_set {
         where
             $($xs: Resource,)+
         {
+            type Taken = ($(Option<$xs>,)+);
+
             fn insert(self, world: &mut World) {
                 $(
                     world.set_res(self.$i);
                 )+
             }
 
-            fn take(world: &mut World) {
-                $(
-                    world.take_res::<$xs>();
-                )+
+            fn take(world: &mut World) -> Self::Taken {
+                ($(
+                    world.take_res::<$xs>(),
+                )+)
             }
         }
     };
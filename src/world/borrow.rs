@@ -0,0 +1,46 @@
+//! Borrow location tracking for debugging "already borrowed" errors
+//!
+//! Locations are recorded only with `debug-borrow-tracking` feature, and reported by the
+//! `AlreadyBorrowedAt` variants of the borrow errors.
+//!
+//! The locations come from `#[track_caller]`, which doesn't go through
+//! [`AutoFetch`](crate::world::fetch::AutoFetch). So borrows made by systems are recorded at the
+//! fetch implementation inside `fetch.rs`, not at the system.
+
+use std::{any::TypeId, panic::Location};
+
+#[cfg(feature = "debug-borrow-tracking")]
+use {rustc_hash::FxHashMap, std::sync::Mutex};
+
+/// Records the last borrow locations by type
+#[derive(Debug, Default)]
+pub(crate) struct BorrowTracker {
+    #[cfg(feature = "debug-borrow-tracking")]
+    locations: Mutex<FxHashMap<TypeId, &'static Location<'static>>>,
+}
+
+#[cfg(feature = "debug-borrow-tracking")]
+impl BorrowTracker {
+    #[track_caller]
+    pub(crate) fn record(&self, ty: TypeId) {
+        self.locations
+            .lock()
+            .unwrap()
+            .insert(ty, Location::caller());
+    }
+
+    pub(crate) fn location(&self, ty: TypeId) -> Option<&'static Location<'static>> {
+        self.locations.lock().unwrap().get(&ty).copied()
+    }
+}
+
+#[cfg(not(feature = "debug-borrow-tracking"))]
+impl BorrowTracker {
+    #[inline]
+    pub(crate) fn record(&self, _ty: TypeId) {}
+
+    #[inline]
+    pub(crate) fn location(&self, _ty: TypeId) -> Option<&'static Location<'static>> {
+        None
+    }
+}
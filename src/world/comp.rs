@@ -7,7 +7,9 @@ pub use toecs_derive::Component;
 use std::{
     any::{self, TypeId},
     cell::RefCell,
-    fmt, mem, ops, slice,
+    fmt, mem, ops,
+    panic::Location,
+    slice,
 };
 
 use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
@@ -16,7 +18,8 @@ use rustc_hash::FxHashMap;
 use thiserror::Error;
 
 use crate::world::{
-    ent::Entity,
+    borrow::BorrowTracker,
+    ent::{Entity, MapEntities},
     sparse::{DenseIndex, SparseIndex, SparseSet},
 };
 
@@ -27,17 +30,47 @@ impl_downcast!(Component);
 
 /// Resource borrow error type
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum BorrowError {
     #[error("component of type `{0}` is not registered")]
     NotRegistered(&'static str),
     #[error("component pool of type `{0}` is already borrowed")]
     AlreadyBorrowed(&'static str),
+    /// `AlreadyBorrowed` with the location of the last borrow. It's only returned with
+    /// `debug-borrow-tracking` feature.
+    #[error("component pool of type `{0}` is already borrowed (last borrowed at {1})")]
+    AlreadyBorrowedAt(&'static str, &'static Location<'static>),
 }
 
+impl BorrowError {
+    fn already_borrowed(ty: &'static str, loc: Option<&'static Location<'static>>) -> Self {
+        match loc {
+            Some(loc) => Self::AlreadyBorrowedAt(ty, loc),
+            None => Self::AlreadyBorrowed(ty),
+        }
+    }
+
+    /// Returns the location of the last borrow. It's always `None` without
+    /// `debug-borrow-tracking` feature.
+    pub fn location(&self) -> Option<&'static Location<'static>> {
+        match self {
+            Self::AlreadyBorrowedAt(_, loc) => Some(loc),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned when cloning component pools that are not registered with `Clone`
+/// implementation. It contains the type names of the pools.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("component pools are not registered with `Clone` implementation: {0:?}")]
+pub struct CloneError(pub Vec<&'static str>);
+
 /// SoA storage of components backed by sparse sets
 #[derive(Debug, Default)]
 pub struct ComponentPoolMap {
     cells: FxHashMap<TypeId, AtomicRefCell<ErasedPool>>,
+    tracker: BorrowTracker,
 }
 
 #[derive(Debug)]
@@ -46,11 +79,109 @@ struct ErasedPool {
     #[allow(unused)]
     of_type: &'static str,
     erased: Box<dyn ErasedComponentPool>,
+    /// Entity remapper, present if it's registered with [`MapEntities`] implementation
+    map_entities: Option<MapEntitiesFn>,
+    /// Upcast of a component to [`MapEntities`], present along with `map_entities`
+    get_map_entities: Option<GetMapEntitiesFn>,
+    /// Pool cloner, present if it's registered with `Clone` implementation
+    clone: Option<CloneFn>,
+    /// Pool differ, present if it's registered with `Clone` and `PartialEq` implementation
+    diff: Option<DiffFn>,
+}
+
+type MapEntitiesFn = fn(&mut dyn ErasedComponentPool, &mut dyn FnMut(Entity) -> Entity);
+type GetMapEntitiesFn = fn(&mut dyn ErasedComponentPool, Entity) -> Option<&mut dyn MapEntities>;
+type CloneFn = fn(&dyn ErasedComponentPool) -> Box<dyn ErasedComponentPool>;
+type DiffFn =
+    fn(Option<&dyn ErasedComponentPool>, &dyn ErasedComponentPool) -> Box<dyn ErasedPoolDiff>;
+
+/// Cloned component pool
+#[derive(Debug)]
+pub(crate) struct PoolSnapshot {
+    of_type: &'static str,
+    erased: Box<dyn ErasedComponentPool>,
+    diff: Option<DiffFn>,
 }
 
-/// Upcast of `ComponentPool<T>`s
-pub(crate) trait ErasedComponentPool: Downcast + fmt::Debug {
+impl PoolSnapshot {
+    /// Returns the changes from `old` to `self`
+    ///
+    /// # Panics
+    /// Panics if the component pool is not registered with `PartialEq` implementation.
+    pub(crate) fn diff_from(&self, old: Option<&PoolSnapshot>) -> Box<dyn ErasedPoolDiff> {
+        let diff = self.diff.unwrap_or_else(|| {
+            panic!(
+                "component pool of type `{}` is not registered with `PartialEq` implementation",
+                self.of_type
+            )
+        });
+        diff(old.map(|old| &*old.erased), &*self.erased)
+    }
+}
+
+/// Changes of a component pool between two snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolDiff<T> {
+    /// Components added or changed
+    pub inserted: Vec<(Entity, T)>,
+    /// Entities of which component is removed
+    pub removed: Vec<Entity>,
+}
+
+/// Upcast of `PoolDiff<T>`s
+pub(crate) trait ErasedPoolDiff: Downcast + fmt::Debug {
+    fn is_empty(&self) -> bool;
+    fn apply(&self, pool: &mut dyn ErasedComponentPool);
+}
+
+impl_downcast!(ErasedPoolDiff);
+
+impl<T: Component + Clone> ErasedPoolDiff for PoolDiff<T> {
+    fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.removed.is_empty()
+    }
+
+    fn apply(&self, pool: &mut dyn ErasedComponentPool) {
+        let pool = pool
+            .downcast_mut::<ComponentPool<T>>()
+            .unwrap_or_else(|| unreachable!());
+
+        for ent in &self.removed {
+            pool.swap_remove(*ent);
+        }
+
+        for (ent, comp) in &self.inserted {
+            pool.insert(*ent, comp.clone());
+        }
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Upcast of `ComponentPool<T>`s. See [`ComponentPoolMap::register_erased`].
+///
+/// This trait is sealed and only implemented by [`ComponentPool`].
+pub trait ErasedComponentPool: Downcast + fmt::Debug + sealed::Sealed {
+    /// Returns the `TypeId` of the component type
+    fn erased_type_id(&self) -> TypeId;
+    /// Returns the name of the component type
+    fn erased_type_name(&self) -> &'static str;
+    fn erased_contains(&self, entity: Entity) -> bool;
     fn erased_remove(&mut self, entity: Entity);
+    fn erased_memory_usage(&self) -> usize;
+    /// Returns the entities in the dense order, including tombstones of a frozen pool
+    fn erased_entities(&self) -> &[Entity];
+    /// Creates an empty pool of the same type
+    fn erased_empty(&self) -> Box<dyn ErasedComponentPool>;
+    /// Moves the components into a pool of the same type, mapping the entities with `f`.
+    /// Components of entities mapped to `None` are dropped.
+    fn erased_move_into(
+        &mut self,
+        dst: &mut dyn ErasedComponentPool,
+        f: &mut dyn FnMut(Entity) -> Option<Entity>,
+    );
 }
 
 impl_downcast!(ErasedComponentPool);
@@ -68,32 +199,349 @@ impl ComponentPoolMap {
         self.cells.contains_key(&ty)
     }
 
+    /// Returns the type name of a registered component. This is safe because it has exclusive
+    /// access.
+    pub(crate) fn type_name_raw(&mut self, ty: TypeId) -> Option<&'static str> {
+        Some(self.cells.get_mut(&ty)?.get_mut().of_type)
+    }
+
+    /// Returns true if the component pool of type `ty` is registered and contains the entity.
+    /// This is safe because it has exclusive access.
+    pub(crate) fn contains_raw(&mut self, ty: TypeId, ent: Entity) -> bool {
+        self.cells
+            .get_mut(&ty)
+            .is_some_and(|cell| cell.get_mut().erased.erased_contains(ent))
+    }
+
     /// Registers a component pool for type `T`. Returns true if it was already registered.
     pub fn register<T: Component>(&mut self) -> bool {
+        self.register_with_capacity::<T>(0)
+    }
+
+    /// Registers a component pool for type `T` with the capacity reserved. Returns true if it was
+    /// already registered.
+    pub fn register_with_capacity<T: Component>(&mut self, cap: usize) -> bool {
         let ty = TypeId::of::<T>();
         if self.cells.contains_key(&ty) {
             return true;
         }
 
         let pool = ErasedPool {
-            erased: Box::new(ComponentPool::<T>::default()),
+            erased: Box::new(ComponentPool::<T>::with_capacity(cap)),
             of_type: any::type_name::<T>(),
+            map_entities: None,
+            get_map_entities: None,
+            clone: None,
+            diff: None,
+        };
+
+        self.cells.insert(ty, AtomicRefCell::new(pool));
+        false
+    }
+
+    /// Registers a component pool for a type only known at runtime, such as on deserialization.
+    /// `make` is called only if it's not registered yet. Returns true if it was already
+    /// registered.
+    ///
+    /// # Panics
+    /// Panics if the pool made with `make` is not of type `ty`.
+    pub fn register_erased(
+        &mut self,
+        ty: TypeId,
+        make: impl FnOnce() -> Box<dyn ErasedComponentPool>,
+    ) -> bool {
+        if self.cells.contains_key(&ty) {
+            return true;
+        }
+
+        let erased = make();
+        assert_eq!(
+            erased.erased_type_id(),
+            ty,
+            "given component pool of type `{}` doesn't match the `TypeId`",
+            erased.erased_type_name(),
+        );
+
+        let pool = ErasedPool {
+            of_type: erased.erased_type_name(),
+            erased,
+            map_entities: None,
+            get_map_entities: None,
+            clone: None,
+            diff: None,
         };
 
         self.cells.insert(ty, AtomicRefCell::new(pool));
         false
     }
 
+    /// Registers a component pool for type `T` along with its entity remapper
+    pub fn register_map_entities<T: Component + MapEntities>(&mut self) {
+        fn map_entities<T: Component + MapEntities>(
+            pool: &mut dyn ErasedComponentPool,
+            f: &mut dyn FnMut(Entity) -> Entity,
+        ) {
+            let pool = pool
+                .downcast_mut::<ComponentPool<T>>()
+                .unwrap_or_else(|| unreachable!());
+            pool.as_mut_slice()
+                .iter_mut()
+                .for_each(|comp| comp.map_entities(f));
+        }
+
+        fn get_map_entities<T: Component + MapEntities>(
+            pool: &mut dyn ErasedComponentPool,
+            ent: Entity,
+        ) -> Option<&mut dyn MapEntities> {
+            let pool = pool
+                .downcast_mut::<ComponentPool<T>>()
+                .unwrap_or_else(|| unreachable!());
+            pool.get_mut(ent).map(|comp| comp as &mut dyn MapEntities)
+        }
+
+        self.register::<T>();
+        let pool = self.cells.get_mut(&TypeId::of::<T>()).unwrap().get_mut();
+        pool.map_entities = Some(map_entities::<T>);
+        pool.get_map_entities = Some(get_map_entities::<T>);
+    }
+
+    /// Registers a component pool for type `T` along with its cloner, which is required to take
+    /// snapshots
+    pub fn register_clone<T: Component + Clone>(&mut self) {
+        fn clone<T: Component + Clone>(
+            pool: &dyn ErasedComponentPool,
+        ) -> Box<dyn ErasedComponentPool> {
+            let pool = pool
+                .downcast_ref::<ComponentPool<T>>()
+                .unwrap_or_else(|| unreachable!());
+            Box::new(pool.clone())
+        }
+
+        self.register::<T>();
+        let pool = self.cells.get_mut(&TypeId::of::<T>()).unwrap().get_mut();
+        pool.clone = Some(clone::<T>);
+    }
+
+    /// Registers a component pool for type `T` along with its cloner and differ, which are
+    /// required to take diffs of snapshots
+    pub fn register_diff<T: Component + Clone + PartialEq>(&mut self) {
+        fn diff<T: Component + Clone + PartialEq>(
+            old: Option<&dyn ErasedComponentPool>,
+            new: &dyn ErasedComponentPool,
+        ) -> Box<dyn ErasedPoolDiff> {
+            let old = old.map(|old| {
+                old.downcast_ref::<ComponentPool<T>>()
+                    .unwrap_or_else(|| unreachable!())
+            });
+            let new = new
+                .downcast_ref::<ComponentPool<T>>()
+                .unwrap_or_else(|| unreachable!());
+
+            let inserted = new
+                .iter_dense()
+                .filter(|(ent, comp)| old.and_then(|old| old.get(*ent)) != Some(*comp))
+                .map(|(ent, comp)| (ent, comp.clone()))
+                .collect();
+
+            let removed = old
+                .map(|old| {
+                    old.iter_dense()
+                        .map(|(ent, _)| ent)
+                        .filter(|ent| !new.contains(*ent))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Box::new(PoolDiff { inserted, removed })
+        }
+
+        self.register_clone::<T>();
+        let pool = self.cells.get_mut(&TypeId::of::<T>()).unwrap().get_mut();
+        pool.diff = Some(diff::<T>);
+    }
+
+    /// Clones every component pool
+    ///
+    /// # Panics
+    /// Panics if any component pool is not registered with `Clone` implementation or if it's
+    /// mutably borrowed.
+    pub(crate) fn snapshot(&self) -> FxHashMap<TypeId, PoolSnapshot> {
+        self.cells
+            .iter()
+            .map(|(ty, cell)| {
+                let pool = cell.borrow();
+                let clone = pool.clone.unwrap_or_else(|| {
+                    panic!(
+                        "component pool of type `{}` is not registered with `Clone` implementation",
+                        pool.of_type
+                    )
+                });
+                let snapshot = PoolSnapshot {
+                    of_type: pool.of_type,
+                    erased: clone(&*pool.erased),
+                    diff: pool.diff,
+                };
+                (*ty, snapshot)
+            })
+            .collect()
+    }
+
+    /// Clones the component pools along with their hooks
+    ///
+    /// # Panics
+    /// Panics if any component pool is mutably borrowed.
+    pub(crate) fn try_clone(&self) -> Result<Self, CloneError> {
+        let missing = self
+            .cells
+            .values()
+            .map(|cell| cell.borrow())
+            .filter(|pool| pool.clone.is_none())
+            .map(|pool| pool.of_type)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(CloneError(missing));
+        }
+
+        let cells = self
+            .cells
+            .iter()
+            .map(|(ty, cell)| {
+                let pool = cell.borrow();
+                let clone = pool.clone.unwrap_or_else(|| unreachable!());
+                let pool = ErasedPool {
+                    of_type: pool.of_type,
+                    erased: clone(&*pool.erased),
+                    map_entities: pool.map_entities,
+                    get_map_entities: pool.get_map_entities,
+                    clone: pool.clone,
+                    diff: pool.diff,
+                };
+                (*ty, AtomicRefCell::new(pool))
+            })
+            .collect();
+
+        Ok(Self {
+            cells,
+            tracker: Default::default(),
+        })
+    }
+
+    /// Restores the component pools. Pools registered after taking the snapshot are cleared with
+    /// the current entities.
+    pub(crate) fn restore(&mut self, mut pools: FxHashMap<TypeId, PoolSnapshot>, ents: &[Entity]) {
+        for (ty, cell) in &mut self.cells {
+            let pool = cell.get_mut();
+            match pools.remove(ty) {
+                Some(snapshot) => pool.erased = snapshot.erased,
+                None => ents.iter().for_each(|ent| pool.erased.erased_remove(*ent)),
+            }
+        }
+    }
+
+    /// Applies changes to the component pools
+    pub(crate) fn apply_diff(&mut self, diffs: &FxHashMap<TypeId, Box<dyn ErasedPoolDiff>>) {
+        for (ty, diff) in diffs {
+            let cell = self
+                .cells
+                .get_mut(ty)
+                .unwrap_or_else(|| panic!("component pool of the diff is not registered"));
+            diff.apply(&mut *cell.get_mut().erased);
+        }
+    }
+
+    /// Remaps entities stored in the components that are registered with [`MapEntities`]
+    /// implementation
+    pub fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+        for cell in self.cells.values_mut() {
+            let pool = cell.get_mut();
+            if let Some(map_entities) = pool.map_entities {
+                map_entities(&mut *pool.erased, f);
+            }
+        }
+    }
+
+    /// Moves the component pools of another map into this map, mapping the entities and the
+    /// [`MapEntities`] components with `f`. Component types missing in this map are registered
+    /// along with their hooks.
+    ///
+    /// Components of entities mapped to `None` are dropped, and so are [`MapEntities`] components
+    /// referring to such entities.
+    pub(crate) fn merge(
+        &mut self,
+        other: ComponentPoolMap,
+        f: &mut dyn FnMut(Entity) -> Option<Entity>,
+    ) {
+        for (ty, cell) in other.cells {
+            let mut src = cell.into_inner();
+            let dst = self.cells.entry(ty).or_insert_with(|| {
+                AtomicRefCell::new(ErasedPool {
+                    of_type: src.of_type,
+                    erased: src.erased.erased_empty(),
+                    map_entities: None,
+                    get_map_entities: None,
+                    clone: None,
+                    diff: None,
+                })
+            });
+            let dst = dst.get_mut();
+
+            if dst.map_entities.is_none() {
+                dst.map_entities = src.map_entities;
+                dst.get_map_entities = src.get_map_entities;
+            }
+            dst.clone = dst.clone.or(src.clone);
+            dst.diff = dst.diff.or(src.diff);
+
+            // remap the references before the components are mixed with ours
+            if let Some(get) = dst.get_map_entities {
+                let ents = src.erased.erased_entities().to_vec();
+                for ent in ents {
+                    let mut dangling = false;
+                    if let Some(comp) = get(&mut *src.erased, ent) {
+                        comp.map_entities(&mut |e| {
+                            f(e).unwrap_or_else(|| {
+                                dangling = true;
+                                e
+                            })
+                        });
+                    }
+                    if dangling {
+                        src.erased.erased_remove(ent);
+                    }
+                }
+            }
+            src.erased.erased_move_into(&mut *dst.erased, f);
+        }
+    }
+
+    /// Visits the components of an entity that are registered with [`MapEntities`]
+    /// implementation
+    pub fn visit_map_entities_of(&mut self, ent: Entity, f: &mut dyn FnMut(&mut dyn MapEntities)) {
+        for cell in self.cells.values_mut() {
+            let pool = cell.get_mut();
+            if let Some(get) = pool.get_map_entities {
+                if let Some(comp) = get(&mut *pool.erased, ent) {
+                    f(comp);
+                }
+            }
+        }
+    }
+
     /// Tries to get an immutable access to a component pool
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn try_borrow<T: Component>(&self) -> Result<Comp<T>, BorrowError> {
         let cell = self
             .cells
             .get(&TypeId::of::<T>())
             .ok_or_else(|| BorrowError::NotRegistered(any::type_name::<T>()))?;
 
-        let inner = cell
-            .try_borrow()
-            .map_err(|_| BorrowError::AlreadyBorrowed(any::type_name::<T>()))?;
+        let inner = cell.try_borrow().map_err(|_| {
+            BorrowError::already_borrowed(
+                any::type_name::<T>(),
+                self.tracker.location(TypeId::of::<T>()),
+            )
+        })?;
+        self.tracker.record(TypeId::of::<T>());
 
         let borrow = AtomicRef::map(inner, |pool| {
             pool.erased.downcast_ref::<ComponentPool<T>>().unwrap()
@@ -103,15 +551,20 @@ impl ComponentPoolMap {
     }
 
     /// Tries to get a mutable access to a component pool
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn try_borrow_mut<T: Component>(&self) -> Result<CompMut<T>, BorrowError> {
         let cell = self
             .cells
             .get(&TypeId::of::<T>())
             .ok_or_else(|| BorrowError::NotRegistered(any::type_name::<T>()))?;
 
-        let inner = cell
-            .try_borrow_mut()
-            .map_err(|_| BorrowError::AlreadyBorrowed(any::type_name::<T>()))?;
+        let inner = cell.try_borrow_mut().map_err(|_| {
+            BorrowError::already_borrowed(
+                any::type_name::<T>(),
+                self.tracker.location(TypeId::of::<T>()),
+            )
+        })?;
+        self.tracker.record(TypeId::of::<T>());
 
         let borrow = AtomicRefMut::map(inner, |pool| {
             pool.erased
@@ -127,12 +580,62 @@ impl ComponentPoolMap {
         Some(cell.get_mut().erased.downcast_mut().unwrap())
     }
 
+    /// Groups entities by the set of component types they have. Returns pairs of sorted component
+    /// types and the number of entities, in descending order of the count.
+    ///
+    /// # Panics
+    /// Panics if any component pool is mutably borrowed.
+    pub(crate) fn archetype_summary(&self, ents: &[Entity]) -> Vec<(Vec<TypeId>, usize)> {
+        let mut pools = self
+            .cells
+            .iter()
+            .map(|(ty, cell)| (*ty, cell.borrow()))
+            .collect::<Vec<_>>();
+        pools.sort_by_key(|(ty, _)| *ty);
+
+        let mut counts = FxHashMap::<Vec<TypeId>, usize>::default();
+        for ent in ents {
+            let types = pools
+                .iter()
+                .filter(|(_, pool)| pool.erased.erased_contains(*ent))
+                .map(|(ty, _)| *ty)
+                .collect::<Vec<_>>();
+            *counts.entry(types).or_default() += 1;
+        }
+
+        let mut summary = counts.into_iter().collect::<Vec<_>>();
+        summary.sort_by(|(t1, n1), (t2, n2)| n2.cmp(n1).then_with(|| t1.cmp(t2)));
+        summary
+    }
+
+    /// Returns type names and heap memory usages of the component pools in bytes
+    ///
+    /// # Panics
+    /// Panics if any component pool is mutably borrowed.
+    pub(crate) fn memory_report(&self) -> Vec<(&'static str, usize)> {
+        self.cells
+            .values()
+            .map(|cell| {
+                let pool = cell.borrow();
+                (pool.of_type, pool.erased.erased_memory_usage())
+            })
+            .collect()
+    }
+
     pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut dyn ErasedComponentPool> {
         self.cells
             .values_mut()
             .map(|cell| &mut *cell.get_mut().erased)
     }
 
+    /// Enumerates type names and component pools. This is safe because it has exclusive access.
+    pub(crate) fn iter_debug(&mut self) -> impl Iterator<Item = (&'static str, &dyn fmt::Debug)> {
+        self.cells.values_mut().map(|cell| {
+            let pool = cell.get_mut();
+            (pool.of_type, &pool.erased as &dyn fmt::Debug)
+        })
+    }
+
     /// Returns a debug display. This is safe because it has exclusive access.
     pub fn display(&mut self) -> ComponentPoolMapDisplay {
         let mut map = ComponentPoolMap::default();
@@ -173,20 +676,88 @@ impl<'r> fmt::Debug for ComponentPoolMapDisplay<'r> {
     }
 }
 
+/// Returns true if the dense slot is not mapped from the entity, i.e., the slot is a tombstone of a
+/// frozen pool
+pub(crate) fn is_tombstone(to_dense: &[Option<DenseIndex>], ent: Entity, slot: usize) -> bool {
+    to_dense
+        .get(ent.0.to_usize())
+        .and_then(|dense| dense.as_ref())
+        .is_none_or(|dense| dense.to_usize() != slot)
+}
+
 /// Sparse set of components of type T
+#[derive(Clone)]
 pub struct ComponentPool<T> {
     set: SparseSet<T>,
+    /// If true, removed components are left in the dense arrays as tombstones
+    frozen: bool,
+    /// Number of the tombstones in the dense arrays
+    n_tombstones: usize,
 }
 
+impl<T: Component> sealed::Sealed for ComponentPool<T> {}
+
 impl<T: Component> ErasedComponentPool for ComponentPool<T> {
+    fn erased_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn erased_type_name(&self) -> &'static str {
+        any::type_name::<T>()
+    }
+
+    fn erased_contains(&self, entity: Entity) -> bool {
+        self.contains(entity)
+    }
+
     fn erased_remove(&mut self, entity: Entity) {
-        self.swap_remove(entity);
+        if self.frozen {
+            if self.set.unmap(entity.0).is_some() {
+                self.n_tombstones += 1;
+            }
+        } else {
+            self.swap_remove(entity);
+        }
+    }
+
+    fn erased_memory_usage(&self) -> usize {
+        self.memory_usage()
+    }
+
+    fn erased_entities(&self) -> &[Entity] {
+        self.entities()
+    }
+
+    fn erased_empty(&self) -> Box<dyn ErasedComponentPool> {
+        Box::new(Self::default())
+    }
+
+    fn erased_move_into(
+        &mut self,
+        dst: &mut dyn ErasedComponentPool,
+        f: &mut dyn FnMut(Entity) -> Option<Entity>,
+    ) {
+        let dst = dst
+            .downcast_mut::<ComponentPool<T>>()
+            .unwrap_or_else(|| unreachable!());
+        self.compact();
+        let (sparse, comps) = mem::take(&mut self.set).into_dense();
+        for (sparse, comp) in sparse.into_iter().zip(comps) {
+            if let Some(ent) = f(Entity(sparse)) {
+                dst.insert(ent, comp);
+            }
+        }
     }
 }
 
+/// The alternate form (`{:#?}`) prints entities along with the components.
 impl<T: Component> fmt::Debug for ComponentPool<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_list().entries(self.set.as_slice()).finish()
+        if f.alternate() {
+            f.debug_map().entries(self.iter_dense()).finish()
+        } else {
+            f.debug_list().entries(self.set.as_slice()).finish()
+        }
     }
 }
 
@@ -194,19 +765,75 @@ impl<T> Default for ComponentPool<T> {
     fn default() -> Self {
         Self {
             set: Default::default(),
+            frozen: false,
+            n_tombstones: 0,
         }
     }
 }
 
 impl<T> ComponentPool<T> {
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            set: SparseSet::with_capacity(cap),
+            frozen: false,
+            n_tombstones: 0,
+        }
+    }
+
+    /// Returns the number of components. Tombstones of a frozen pool are not counted.
+    pub fn len(&self) -> usize {
+        self.set.len() - self.n_tombstones
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.set.capacity()
+    }
+
+    /// Returns the heap memory allocated by the pool in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.set.memory_usage()
+    }
+
+    /// Shrinks the capacity of the pool as much as possible
+    pub fn shrink_to_fit(&mut self) {
+        self.set.shrink_to_fit();
+    }
+
     pub fn contains(&self, ent: Entity) -> bool {
         self.set.contains(ent.0)
     }
 
+    /// Returns true if every entity has a component in this pool
+    pub fn contains_all(&self, ents: &[Entity]) -> bool {
+        ents.iter().all(|e| self.contains(*e))
+    }
+
+    /// Returns an iterator of entities that have a component in this pool
+    pub fn filter_present<'a>(&'a self, ents: &'a [Entity]) -> impl Iterator<Item = Entity> + 'a {
+        ents.iter().copied().filter(|e| self.contains(*e))
+    }
+
     pub fn get(&self, ent: Entity) -> Option<&T> {
         self.set.get(ent.0)
     }
 
+    /// Returns the components of the entities at once
+    pub fn get_many<const N: usize>(&self, ents: [Entity; N]) -> [Option<&T>; N] {
+        ents.map(|ent| self.get(ent))
+    }
+
+    /// Returns a clone of the component, so that the pool borrow can be released soon
+    pub fn get_cloned(&self, ent: Entity) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.get(ent).cloned()
+    }
+
     pub fn get_mut(&mut self, ent: Entity) -> Option<&mut T> {
         self.set.get_mut(ent.0)
     }
@@ -218,6 +845,15 @@ impl<T> ComponentPool<T> {
         unsafe { Some((&mut *a, &mut *b)) }
     }
 
+    /// Checked version of [`get2_mut`](Self::get2_mut). Returns `None` if the two entities share
+    /// the same slot or either of them doesn't have the component.
+    pub fn get2_mut_checked(&mut self, a: Entity, b: Entity) -> Option<(&mut T, &mut T)> {
+        if a.0.raw() == b.0.raw() {
+            return None;
+        }
+        self.get2_mut(a, b)
+    }
+
     pub fn as_slice(&self) -> &[T] {
         self.set.as_slice()
     }
@@ -226,10 +862,34 @@ impl<T> ComponentPool<T> {
         self.set.as_mut_slice()
     }
 
+    /// Returns an iterator of chunks of the dense components. The components are stored
+    /// contiguously, so the chunks can be fed to SIMD code. The last chunk may be shorter.
+    ///
+    /// While the pool is [frozen](Self::freeze), the chunks include the tombstones.
+    ///
+    /// # Panics
+    /// Panics if `n` is zero.
+    pub fn chunks(&self, n: usize) -> slice::Chunks<T> {
+        self.as_slice().chunks(n)
+    }
+
+    /// Mutable version of [`chunks`](Self::chunks)
+    ///
+    /// # Panics
+    /// Panics if `n` is zero.
+    pub fn chunks_mut(&mut self, n: usize) -> slice::ChunksMut<T> {
+        self.as_mut_slice().chunks_mut(n)
+    }
+
     pub fn entities(&self) -> &[Entity] {
         Self::to_entities(self.set.indices())
     }
 
+    /// Returns the entity that owns the component at the dense slot
+    pub fn entity_at(&self, slot: usize) -> Option<Entity> {
+        self.entities().get(slot).copied()
+    }
+
     pub fn as_slice_with_entities(&self) -> (&[Entity], &[T]) {
         let (sparse, comps) = self.set.as_slice_with_indices();
         (Self::to_entities(sparse), comps)
@@ -240,6 +900,32 @@ impl<T> ComponentPool<T> {
         (Self::to_entities(sparse), comps)
     }
 
+    /// Returns an iterator of entities and their components in the dense order. This is the fastest
+    /// way to iterate through a single pool, and handy for collecting them into a map. Tombstones
+    /// of a frozen pool are skipped.
+    pub fn iter_dense(&self) -> impl Iterator<Item = (Entity, &T)> {
+        let (ents, comps) = self.as_slice_with_entities();
+        ents.iter()
+            .copied()
+            .zip(comps.iter())
+            .enumerate()
+            .filter(move |(slot, _)| !self.is_tombstone(*slot))
+            .map(|(_, pair)| pair)
+    }
+
+    /// Returns an iterator of entities and mutable references to their components in the dense
+    /// order. Tombstones of a frozen pool are skipped.
+    pub fn iter_mut_with_entities(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        let frozen = self.frozen;
+        let (to_dense, ents, comps) = self.parts_mut();
+        ents.iter()
+            .copied()
+            .zip(comps.iter_mut())
+            .enumerate()
+            .filter(move |(slot, (ent, _))| !(frozen && is_tombstone(to_dense, *ent, *slot)))
+            .map(|(_, pair)| pair)
+    }
+
     fn to_entities(sparse: &[SparseIndex]) -> &[Entity] {
         // SAFE: `Entity` is a transparent wrapper of `SparseIndex`
         unsafe { slice::from_raw_parts(sparse as *const _ as *const _, sparse.len()) }
@@ -249,10 +935,92 @@ impl<T> ComponentPool<T> {
         self.set.insert(ent.0, comp)
     }
 
+    /// # Panics
+    /// Panics if the pool is frozen and contains the entity, because the component can't be taken
+    /// out without reordering the dense arrays.
     pub(crate) fn swap_remove(&mut self, ent: Entity) -> Option<T> {
+        assert!(
+            !(self.frozen && self.contains(ent)),
+            "unable to take a component out of a frozen pool"
+        );
         self.set.swap_remove(ent.0)
     }
 
+    /// Swap-removes the component of an entity, returning it with the entity whose component is
+    /// moved into the vacated dense slot. The moved entity is `None` if the removed component was
+    /// the last one.
+    ///
+    /// This is useful for keeping external arrays parallel to the dense slots.
+    ///
+    /// # Panics
+    /// Panics if the pool is frozen and contains the entity.
+    pub fn swap_remove_tracked(&mut self, ent: Entity) -> Option<(T, Option<Entity>)> {
+        assert!(
+            !(self.frozen && self.contains(ent)),
+            "unable to take a component out of a frozen pool"
+        );
+        self.set
+            .swap_remove_tracked(ent.0)
+            .map(|(comp, swapped)| (comp, swapped.map(Entity)))
+    }
+
+    /// Freezes the dense arrays so that the dense slots stay valid, for example, while external
+    /// libraries refer to [`as_slice_with_entities`](Self::as_slice_with_entities).
+    ///
+    /// While frozen, despawned components are left in the dense arrays as tombstones. They're not
+    /// visible to `get`, `contains`, `len`, the iterators or the queries, but they're still in the
+    /// dense slices such as [`as_slice`](Self::as_slice), [`entities`](Self::entities) and
+    /// [`chunks`](Self::chunks). Check the slots with [`is_tombstone`](Self::is_tombstone) when
+    /// reading the slices. Taking out components such as with `World::remove` panics.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Returns true if the component at the dense slot is removed while the pool is frozen
+    pub fn is_tombstone(&self, slot: usize) -> bool {
+        self.frozen && self.set.is_unmapped(slot)
+    }
+
+    /// Removes the tombstones from the dense arrays, keeping the order of the other components
+    pub fn compact(&mut self) {
+        if self.n_tombstones > 0 {
+            self.set.remove_unmapped();
+            self.n_tombstones = 0;
+        }
+    }
+
+    /// Unfreezes the pool and [`compact`](Self::compact)s it
+    pub fn thaw(&mut self) {
+        self.compact();
+        self.frozen = false;
+    }
+
+    /// Sorts the components in the dense array
+    ///
+    /// # Panics
+    /// Panics if the pool is frozen.
+    pub fn sort_by_key<K: Ord>(&mut self, f: impl FnMut(&T) -> K) {
+        assert!(!self.frozen, "unable to sort a frozen pool");
+        self.set.sort_by_key(f);
+    }
+
+    /// Binary searches the dense array for a key, returning the dense slot. See
+    /// [`slice::binary_search_by_key`] for the return value.
+    ///
+    /// The result is meaningless unless the pool is sorted by the key, such as with
+    /// [`sort_by_key`](Self::sort_by_key).
+    pub fn binary_search_by_key<K: Ord>(
+        &self,
+        key: &K,
+        f: impl FnMut(&T) -> K,
+    ) -> Result<usize, usize> {
+        self.as_slice().binary_search_by_key(key, f)
+    }
+
     pub fn parts(&self) -> (&[Option<DenseIndex>], &[Entity], &[T]) {
         let (a, b, c) = self.set.parts();
         (a, Self::to_entities(b), c)
@@ -264,6 +1032,23 @@ impl<T> ComponentPool<T> {
     }
 }
 
+/// Inserts the components. Components of already-present entities are overwritten.
+impl<T> Extend<(Entity, T)> for ComponentPool<T> {
+    fn extend<I: IntoIterator<Item = (Entity, T)>>(&mut self, iter: I) {
+        for (ent, comp) in iter {
+            self.insert(ent, comp);
+        }
+    }
+}
+
+impl<T> FromIterator<(Entity, T)> for ComponentPool<T> {
+    fn from_iter<I: IntoIterator<Item = (Entity, T)>>(iter: I) -> Self {
+        let mut pool = Self::default();
+        pool.extend(iter);
+        pool
+    }
+}
+
 impl<T> ops::Index<Entity> for ComponentPool<T> {
     type Output = T;
     fn index(&self, index: Entity) -> &Self::Output {
@@ -306,6 +1091,30 @@ impl<'r, T: Component> Comp<'r, T> {
     pub fn deref(&self) -> &ComponentPool<T> {
         <Self as ops::Deref>::deref(self)
     }
+
+    /// Narrows the access to the component of an entity. Returns `None` if the entity doesn't
+    /// have the component.
+    pub fn into_ref(self, ent: Entity) -> Option<CompRef<'r, T>> {
+        if !self.contains(ent) {
+            return None;
+        }
+        let borrow = AtomicRef::map(self.borrow, |pool| pool.get(ent).unwrap());
+        Some(CompRef { borrow })
+    }
+}
+
+/// Immutable access to a component of type `T`. See [`Comp::into_ref`].
+#[derive(Debug)]
+pub struct CompRef<'r, T: Component> {
+    borrow: AtomicRef<'r, T>,
+}
+
+impl<'r, T: Component> ops::Deref for CompRef<'r, T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.borrow.deref()
+    }
 }
 
 /// Mutable access to a component pool of type `T`
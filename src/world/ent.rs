@@ -1,13 +1,16 @@
 //! Entity: ID associated with a set of components
 
 use std::{
-    fmt, slice,
+    fmt, iter, slice,
     sync::atomic::{AtomicU32, Ordering},
 };
 
+use thiserror::Error;
+
 use crate::{
     prelude::ComponentPool,
     world::{comp, sparse::*},
+    World,
 };
 
 /// Identifier that represents an object made of components
@@ -24,7 +27,13 @@ use crate::{
 /// struct Test { a: u32, e: Entity, x: u32 }
 /// assert_eq!(size_of::<Test>(), size_of::<Option<Test>>());
 /// ```
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+///
+/// # Ordering
+///
+/// Entities are ordered by slot, then by generation. It's a structural ordering for deterministic
+/// containers such as `BTreeMap`, not the order of spawning: a recycled slot can be smaller than
+/// slots of older entities.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[repr(transparent)]
 pub struct Entity(pub(crate) SparseIndex);
 
@@ -46,6 +55,13 @@ impl fmt::Display for Entity {
 }
 
 impl Entity {
+    /// Reserved entity that is never alive. It can represent "no target" in relationship
+    /// components, though `Option<Entity>` has the same size.
+    pub const PLACEHOLDER: Self = Self(SparseIndex::new(
+        RawSparseIndex(u32::MAX),
+        Generation::MAX,
+    ));
+
     fn initial(slot: RawSparseIndex) -> Self {
         Self(SparseIndex::initial(slot))
     }
@@ -54,6 +70,11 @@ impl Entity {
         self.0.generation()
     }
 
+    /// Returns the slot index of the entity
+    pub fn index(&self) -> u32 {
+        self.0.raw().0
+    }
+
     pub fn get<'a, T: comp::Component>(&self, comp: &'a ComponentPool<T>) -> Option<&'a T> {
         comp.get(*self)
     }
@@ -66,6 +87,72 @@ impl Entity {
     }
 }
 
+/// Read-only handle of an alive [`Entity`] in a [`World`]
+///
+/// Component pools are borrowed lazily on each access and released soon, so it doesn't hold any
+/// borrow between accesses.
+#[derive(Debug, Clone, Copy)]
+pub struct EntityRef<'w> {
+    world: &'w World,
+    ent: Entity,
+}
+
+impl<'w> EntityRef<'w> {
+    pub(crate) fn new(world: &'w World, ent: Entity) -> Self {
+        Self { world, ent }
+    }
+
+    pub fn id(&self) -> Entity {
+        self.ent
+    }
+
+    /// Returns true if the entity has a component of type `T`
+    ///
+    /// # Panics
+    /// Panics if the component pool is mutably borrowed.
+    pub fn contains<T: comp::Component>(&self) -> bool {
+        match self.world.try_comp::<T>() {
+            Ok(pool) => pool.contains(self.ent),
+            Err(comp::BorrowError::NotRegistered(_)) => false,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Runs a procedure with the component of type `T`, if the entity has it
+    ///
+    /// # Panics
+    /// Panics if the component pool is mutably borrowed.
+    pub fn with<T: comp::Component, Ret>(&self, f: impl FnOnce(&T) -> Ret) -> Option<Ret> {
+        match self.world.try_comp::<T>() {
+            Ok(pool) => pool.get(self.ent).map(f),
+            Err(comp::BorrowError::NotRegistered(_)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
+/// Error on despawning an [`Entity`]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DespawnError {
+    #[error("entity {0} is already despawned")]
+    Dead(Entity),
+    #[error("entity {0} has never been spawned")]
+    NeverSpawned(Entity),
+}
+
+/// Type that stores [`Entity`] IDs, which have to be remapped when they are moved to another
+/// world
+pub trait MapEntities {
+    /// Maps the stored entities with `f`
+    fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity);
+}
+
+impl MapEntities for Entity {
+    fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+        *self = f(*self);
+    }
+}
+
 /// Pool of entities
 ///
 /// # Implementation
@@ -86,15 +173,35 @@ pub struct EntityPool {
     n_reserved: AtomicU32,
 }
 
+impl Clone for EntityPool {
+    fn clone(&self) -> Self {
+        Self {
+            sparse: self.sparse.clone(),
+            dense: self.dense.clone(),
+            first_free: self.first_free,
+            n_free: self.n_free,
+            n_reserved: AtomicU32::new(self.n_reserved.load(Ordering::Relaxed)),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum Entry {
     ToDense(DenseIndex),
     Empty {
-        gen: Generation,
+        /// Generation of the last entity at the slot, or `None` if the slot has never been used
+        gen: Option<Generation>,
         next_free: Option<RawSparseIndex>,
     },
 }
 
+impl Entry {
+    /// Returns the generation of the next entity allocated at the empty slot
+    fn next_generation(gen: Option<Generation>) -> Generation {
+        gen.map_or(Generation::INITIAL, Generation::increment)
+    }
+}
+
 impl fmt::Debug for Entry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -105,7 +212,8 @@ impl fmt::Debug for Entry {
                 dense.generation().to_usize()
             ),
             Self::Empty { gen, next_free } => {
-                write!(f, "Empty({}, {:?})", gen.to_usize(), next_free)
+                let gen = gen.map_or(0, |gen| gen.to_usize());
+                write!(f, "Empty({}, {:?})", gen, next_free)
             }
         }
     }
@@ -126,6 +234,72 @@ impl EntityPool {
         e.generation() == ent.generation()
     }
 
+    /// Returns true if the entity is alive or reserved with [`reserve_atomic`] and not yet
+    /// [`synchronize`]d. Reserved entities are not [`contains`]ed until synchronization.
+    ///
+    /// This is O(number of reserved entities).
+    ///
+    /// [`reserve_atomic`]: Self::reserve_atomic
+    /// [`synchronize`]: Self::synchronize
+    /// [`contains`]: Self::contains
+    pub fn contains_reserved(&self, ent: Entity) -> bool {
+        if self.contains(ent) {
+            return true;
+        }
+
+        let n_reserved = self.n_reserved.load(Ordering::Relaxed) as usize;
+        let slot = ent.0.raw();
+
+        match self.sparse.get(slot.to_usize()) {
+            // reserved slots are pushed after the free slots are used up
+            None => {
+                let n_pushes = n_reserved.saturating_sub(self.n_free);
+                slot.to_usize() < self.sparse.len() + n_pushes
+                    && ent.generation() == Generation::INITIAL
+            }
+            Some(Entry::ToDense(_)) => false,
+            // reserved slots are taken from the head of the free list
+            Some(Entry::Empty { gen, .. }) => {
+                ent.generation() == Entry::next_generation(*gen)
+                    && self
+                        .iter_free()
+                        .take(n_reserved.min(self.n_free))
+                        .any(|free| free == slot)
+            }
+        }
+    }
+
+    /// Returns the generation of the living entity at the slot
+    pub fn generation_of(&self, slot: RawSparseIndex) -> Option<Generation> {
+        match self.sparse.get(slot.to_usize())? {
+            Entry::ToDense(dense) => Some(dense.generation()),
+            Entry::Empty { .. } => None,
+        }
+    }
+
+    /// Removes every entity and resets the generations, so that subsequent allocations start
+    /// with the initial generation.
+    ///
+    /// All the previous [`Entity`] handles become invalid; they can alias new entities.
+    pub fn clear(&mut self) {
+        self.sparse.clear();
+        self.dense.clear();
+        self.first_free = None;
+        self.n_free = 0;
+        *self.n_reserved.get_mut() = 0;
+    }
+
+    /// Returns the number of entities the pool can hold without reallocation
+    pub fn capacity(&self) -> usize {
+        self.dense.capacity()
+    }
+
+    /// Reserves capacity for at least `n` more entities
+    pub fn reserve(&mut self, n: usize) {
+        self.dense.reserve(n);
+        self.sparse.reserve(n.saturating_sub(self.n_free));
+    }
+
     pub fn iter(&self) -> slice::Iter<Entity> {
         self.dense.iter()
     }
@@ -137,7 +311,7 @@ impl EntityPool {
                 _ => unreachable!("free slot bug"),
             };
 
-            let gen = old_gen.increment();
+            let gen = Entry::next_generation(old_gen);
             let entity = Entity(SparseIndex::new(free, gen));
             let dense = DenseIndex::new(RawDenseIndex::from_usize(self.dense.len()), gen);
 
@@ -166,23 +340,28 @@ impl EntityPool {
     }
 
     pub fn dealloc(&mut self, ent: Entity) -> bool {
+        self.try_dealloc(ent).is_ok()
+    }
+
+    /// [`dealloc`](Self::dealloc) with the reason of failure
+    pub fn try_dealloc(&mut self, ent: Entity) -> Result<(), DespawnError> {
         let slot = ent.0.to_usize();
-        if slot > self.sparse.len() - 1 {
-            return false;
+        if slot >= self.sparse.len() {
+            return Err(DespawnError::NeverSpawned(ent));
         }
 
         let dense = match self.sparse[slot] {
             Entry::ToDense(e) => e,
-            Entry::Empty { .. } => return false,
+            Entry::Empty { .. } => return Err(DespawnError::Dead(ent)),
         };
 
         if dense.generation() != ent.generation() {
-            return false;
+            return Err(DespawnError::Dead(ent));
         }
 
         // update sparse/dense array and the free slots
         self.sparse[slot] = Entry::Empty {
-            gen: ent.generation(),
+            gen: Some(ent.generation()),
             next_free: self.first_free,
         };
 
@@ -203,7 +382,93 @@ impl EntityPool {
         self.first_free = Some(RawSparseIndex::from_usize(slot));
         self.n_free += 1;
 
-        true
+        Ok(())
+    }
+
+    /// Allocates an [`Entity`] at a specific slot and generation. Returns `None` if the slot is
+    /// alive, if the generation does not advance or if the generation is `u32::MAX`, which is
+    /// reserved for [`Entity::PLACEHOLDER`] and can't be incremented.
+    ///
+    /// This is for deterministic replays, where two entity pools have to agree on entity IDs. Make
+    /// sure to call [`synchronize`](Self::synchronize) before use.
+    pub fn alloc_at(&mut self, slot: RawSparseIndex, gen: Generation) -> Option<Entity> {
+        debug_assert_eq!(
+            *self.n_reserved.get_mut(),
+            0,
+            "tried to allocate at a slot while having reserved entities"
+        );
+
+        if gen == Generation::MAX {
+            return None;
+        }
+
+        if slot.to_usize() < self.sparse.len() {
+            match self.sparse[slot.to_usize()] {
+                Entry::ToDense(_) => return None,
+                Entry::Empty {
+                    gen: Some(old_gen), ..
+                } if gen <= old_gen => return None,
+                Entry::Empty { .. } => self.unlink_free(slot),
+            }
+        } else {
+            // fill the gap with free slots
+            while self.sparse.len() < slot.to_usize() {
+                let free = RawSparseIndex::from_usize(self.sparse.len());
+                self.sparse.push(Entry::Empty {
+                    gen: None,
+                    next_free: self.first_free,
+                });
+                self.first_free = Some(free);
+                self.n_free += 1;
+            }
+
+            // push a placeholder, which is overwritten soon
+            self.sparse.push(Entry::Empty {
+                gen: None,
+                next_free: None,
+            });
+        }
+
+        let entity = Entity(SparseIndex::new(slot, gen));
+        let dense = DenseIndex::new(RawDenseIndex::from_usize(self.dense.len()), gen);
+
+        self.dense.push(entity);
+        self.sparse[slot.to_usize()] = Entry::ToDense(dense);
+
+        Some(entity)
+    }
+
+    /// Removes a slot from the free list
+    fn unlink_free(&mut self, slot: RawSparseIndex) {
+        let next = match self.sparse[slot.to_usize()] {
+            Entry::Empty { next_free, .. } => next_free,
+            Entry::ToDense(_) => unreachable!("free slot bug: `{slot:?}` is actually filled"),
+        };
+
+        if self.first_free == Some(slot) {
+            self.first_free = next;
+        } else {
+            let mut prev = match self.first_free {
+                Some(free) => free,
+                None => unreachable!("free slot bug: tried to get free slot, but there's none"),
+            };
+
+            loop {
+                match &mut self.sparse[prev.to_usize()] {
+                    Entry::Empty { next_free, .. } if *next_free == Some(slot) => {
+                        *next_free = next;
+                        break;
+                    }
+                    Entry::Empty {
+                        next_free: Some(free),
+                        ..
+                    } => prev = *free,
+                    _ => unreachable!("free slot bug: `{slot:?}` is not in the free list"),
+                }
+            }
+        }
+
+        self.n_free -= 1;
     }
 
     /// Reserves an [`Entity`] only requiring `&self`. Make sure to call
@@ -221,13 +486,21 @@ impl EntityPool {
 
             let gen = match self.sparse[sparse.to_usize()] {
                 Entry::ToDense(_) => unreachable!("free slot bug (atomic)"),
-                Entry::Empty { gen, .. } => gen.increment(),
+                Entry::Empty { gen, .. } => Entry::next_generation(gen),
             };
 
             Entity(SparseIndex::new(sparse, gen))
         }
     }
 
+    /// Iterates through the free slots in the order of allocation
+    fn iter_free(&self) -> impl Iterator<Item = RawSparseIndex> + '_ {
+        iter::successors(self.first_free, |free| match self.sparse[free.to_usize()] {
+            Entry::Empty { next_free, .. } => next_free,
+            Entry::ToDense(_) => unreachable!("free slot bug: `{free:?}` is actually filled"),
+        })
+    }
+
     fn find_nth_free(&self, nth: usize) -> RawSparseIndex {
         let mut sparse = match self.first_free {
             Some(free) => free,
@@ -262,6 +535,9 @@ impl EntityPool {
         let n_reserved = *self.n_reserved.get_mut();
         *self.n_reserved.get_mut() = 0;
 
+        // allocate once
+        self.reserve(n_reserved as usize);
+
         (0..n_reserved).for_each(|_| {
             self.alloc();
         });
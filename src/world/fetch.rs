@@ -2,7 +2,9 @@
 
 pub use toecs_derive::AutoFetch;
 
-use std::{any::TypeId, fmt};
+use std::{any::TypeId, fmt, ops};
+
+use rustc_hash::FxHashSet;
 
 use crate::world::{
     comp::{Comp, CompMut, Component},
@@ -18,11 +20,21 @@ pub enum Access {
     ResMut(TypeId),
     Comp(TypeId),
     CompMut(TypeId),
+    /// Immutable access to the [`EntityPool`]
+    Entities,
+    /// Mutable access to the [`EntityPool`]
+    EntitiesMut,
+    /// Immutable access to everything in the world
+    World,
 }
 
 impl Access {
     pub fn conflicts(self, other: Self) -> bool {
         match (self, other) {
+            (Self::World, Self::ResMut(_) | Self::CompMut(_) | Self::EntitiesMut) => true,
+            (Self::ResMut(_) | Self::CompMut(_) | Self::EntitiesMut, Self::World) => true,
+            (Self::Entities, Self::EntitiesMut) => true,
+            (Self::EntitiesMut, Self::Entities | Self::EntitiesMut) => true,
             (Self::Res(i0), Self::ResMut(i1)) => i0 == i1,
             (Self::ResMut(i0), Self::Res(i1) | Self::ResMut(i1)) => i0 == i1,
             (Self::Comp(i0), Self::CompMut(i1)) => i0 == i1,
@@ -52,6 +64,11 @@ impl AccessSet {
         Self(set)
     }
 
+    /// Returns the accesses as a slice
+    pub fn as_slice(&self) -> &[Access] {
+        &self.0
+    }
+
     /// Checks if the two set of accesses can be got at the same time
     pub fn conflicts(&self, other: &Self) -> bool {
         self.0
@@ -59,6 +76,23 @@ impl AccessSet {
             .any(|a1| other.0.iter().any(|a2| a2.conflicts(*a1)))
     }
 
+    /// Returns accesses in `self` that conflict with some access in `other`
+    pub fn conflicting_types(&self, other: &Self) -> Vec<Access> {
+        self.0
+            .iter()
+            .copied()
+            .filter(|a1| other.0.iter().any(|a2| a2.conflicts(*a1)))
+            .collect()
+    }
+
+    /// Returns accesses in `self` that conflict with another access in `self`
+    pub fn self_conflicting_types(&self) -> Vec<Access> {
+        (0..self.0.len())
+            .filter(|&i| (0..self.0.len()).any(|j| i != j && self.0[i].conflicts(self.0[j])))
+            .map(|i| self.0[i])
+            .collect()
+    }
+
     pub fn self_conflict(&self) -> bool {
         if self.0.len() == 0 {
             return false;
@@ -101,6 +135,72 @@ impl AccessSet {
     pub(crate) fn merge_impl(&mut self, other: &Self) {
         self.0.extend(&other.0);
     }
+
+    /// Converts the accesses into hash sets for fast confliction checks
+    pub fn freeze(&self) -> FrozenAccessSet {
+        let mut set = FrozenAccessSet::default();
+        for access in &self.0 {
+            match *access {
+                Access::Res(ty) => set.res.reads.insert(ty),
+                Access::ResMut(ty) => set.res.writes.insert(ty),
+                Access::Comp(ty) => set.comp.reads.insert(ty),
+                Access::CompMut(ty) => set.comp.writes.insert(ty),
+                Access::Entities => {
+                    set.reads_ents = true;
+                    continue;
+                }
+                Access::EntitiesMut => {
+                    set.writes_ents = true;
+                    continue;
+                }
+                Access::World => {
+                    set.reads_world = true;
+                    continue;
+                }
+            };
+        }
+        set
+    }
+}
+
+/// [`AccessSet`] optimized for confliction checks. See [`AccessSet::freeze`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FrozenAccessSet {
+    res: FrozenAccesses,
+    comp: FrozenAccesses,
+    reads_ents: bool,
+    writes_ents: bool,
+    reads_world: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct FrozenAccesses {
+    reads: FxHashSet<TypeId>,
+    writes: FxHashSet<TypeId>,
+}
+
+impl FrozenAccesses {
+    fn conflicts(&self, other: &Self) -> bool {
+        !self.writes.is_disjoint(&other.writes)
+            || !self.writes.is_disjoint(&other.reads)
+            || !self.reads.is_disjoint(&other.writes)
+    }
+}
+
+impl FrozenAccessSet {
+    /// Checks if the two set of accesses can be got at the same time
+    pub fn conflicts(&self, other: &Self) -> bool {
+        self.res.conflicts(&other.res)
+            || self.comp.conflicts(&other.comp)
+            || (self.writes_ents && (other.reads_ents || other.writes_ents))
+            || (other.writes_ents && self.reads_ents)
+            || (self.reads_world && other.writes_any())
+            || (other.reads_world && self.writes_any())
+    }
+
+    fn writes_any(&self) -> bool {
+        !(self.res.writes.is_empty() && self.comp.writes.is_empty()) || self.writes_ents
+    }
 }
 
 /// Types that are dispatched to systems automatically on run
@@ -160,7 +260,35 @@ impl<'w> AutoFetchImpl<'w> for GatHack<&'_ EntityPool> {
         &w.ents
     }
     fn accesses() -> AccessSet {
-        AccessSet::EMPTY
+        AccessSet::single(Access::Entities)
+    }
+}
+
+/// Immutable access to the whole [`World`] as a system parameter
+///
+/// It declares immutable access to everything in the world, so it conflicts with any mutable
+/// access such as [`ResMut`] or [`CompMut`].
+#[derive(Debug, Clone, Copy)]
+pub struct WorldRef<'w>(pub &'w World);
+
+impl<'w> ops::Deref for WorldRef<'w> {
+    type Target = World;
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl AutoFetch for WorldRef<'_> {
+    type Fetch = GatHack<Self>;
+}
+
+impl<'w> AutoFetchImpl<'w> for GatHack<WorldRef<'_>> {
+    type Item = WorldRef<'w>;
+    unsafe fn fetch(w: &'w World) -> Self::Item {
+        WorldRef(w)
+    }
+    fn accesses() -> AccessSet {
+        AccessSet::single(Access::World)
     }
 }
 
@@ -0,0 +1,219 @@
+//! Hierarchy: parent/child relationships between entities
+
+use std::iter;
+
+use rustc_hash::FxHashSet;
+
+use crate::world::{
+    comp::{BorrowError, Comp, Component},
+    ent::{Entity, MapEntities},
+    World,
+};
+
+/// Parent of an entity
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Parent(pub Entity);
+
+/// Children of an entity
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Children(pub Vec<Entity>);
+
+impl MapEntities for Parent {
+    fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+        self.0.map_entities(f);
+    }
+}
+
+impl MapEntities for Children {
+    fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+        self.0.iter_mut().for_each(|e| e.map_entities(f));
+    }
+}
+
+/// Borrows a component pool, or returns `None` if it's not registered
+///
+/// # Panics
+/// Panics when breaking the aliasing rules.
+fn try_comp<'w, T: Component>(world: &'w World) -> Option<Comp<'w, T>> {
+    match world.try_comp::<T>() {
+        Ok(comp) => Some(comp),
+        Err(BorrowError::NotRegistered(_)) => None,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+/// Iterator of descendants in pre-order. See [`World::descendants`].
+///
+/// Entities are visited only once even if the hierarchy has cycles.
+pub struct Descendants<'w> {
+    children: Option<Comp<'w, Children>>,
+    stack: Vec<Entity>,
+    visited: FxHashSet<Entity>,
+}
+
+impl<'w> Descendants<'w> {
+    pub(crate) fn new(world: &'w World, root: Entity) -> Self {
+        let children = try_comp::<Children>(world);
+        let stack = children
+            .as_ref()
+            .and_then(|cs| cs.get(root))
+            .map(|cs| cs.0.iter().rev().copied().collect())
+            .unwrap_or_default();
+
+        let mut visited = FxHashSet::default();
+        visited.insert(root);
+
+        Self {
+            children,
+            stack,
+            visited,
+        }
+    }
+}
+
+impl<'w> Iterator for Descendants<'w> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(ent) = self.stack.pop() {
+            if !self.visited.insert(ent) {
+                continue;
+            }
+
+            if let Some(cs) = self.children.as_ref().and_then(|cs| cs.get(ent)) {
+                self.stack.extend(cs.0.iter().rev());
+            }
+
+            return Some(ent);
+        }
+
+        None
+    }
+}
+
+/// Iterator of ancestors from the parent to the root. See [`World::ancestors`].
+///
+/// Entities are visited only once even if the hierarchy has cycles.
+pub struct Ancestors<'w> {
+    parents: Option<Comp<'w, Parent>>,
+    current: Entity,
+    visited: FxHashSet<Entity>,
+}
+
+impl<'w> Ancestors<'w> {
+    pub(crate) fn new(world: &'w World, leaf: Entity) -> Self {
+        let mut visited = FxHashSet::default();
+        visited.insert(leaf);
+
+        Self {
+            parents: try_comp::<Parent>(world),
+            current: leaf,
+            visited,
+        }
+    }
+}
+
+impl<'w> Iterator for Ancestors<'w> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let parent = self.parents.as_ref()?.get(self.current)?.0;
+        if !self.visited.insert(parent) {
+            return None;
+        }
+        self.current = parent;
+        Some(parent)
+    }
+}
+
+/// Components of the children of each parent. See [`World::iter_children_components`].
+pub struct ChildrenComponents<'w, T: Component> {
+    children: Option<Comp<'w, Children>>,
+    comps: Comp<'w, T>,
+}
+
+impl<'w, T: Component> ChildrenComponents<'w, T> {
+    pub(crate) fn new(world: &'w World) -> Self {
+        Self {
+            children: try_comp::<Children>(world),
+            comps: world.comp::<T>(),
+        }
+    }
+
+    /// Returns an iterator of parents and their children's components. Children without the
+    /// component are skipped.
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (Entity, impl Iterator<Item = (Entity, &T)> + '_)> + '_ {
+        self.children
+            .iter()
+            .flat_map(|children| children.iter_dense())
+            .map(move |(parent, cs)| {
+                let comps = cs
+                    .0
+                    .iter()
+                    .filter_map(move |child| Some((*child, self.comps.get(*child)?)));
+                (parent, comps)
+            })
+    }
+}
+
+/// Collects an entity and its descendants in pre-order
+pub(crate) fn preorder(world: &World, root: Entity) -> Vec<Entity> {
+    iter::once(root).chain(Descendants::new(world, root)).collect()
+}
+
+/// Removes an entity from its parent's [`Children`]
+pub(crate) fn detach(world: &mut World, child: Entity) {
+    let parent = match world.try_comp::<Parent>() {
+        Ok(parents) => parents.get(child).map(|p| p.0),
+        Err(_) => None,
+    };
+
+    if let Some(parent) = parent {
+        if let Ok(mut children) = world.try_comp_mut::<Children>() {
+            if let Some(cs) = children.get_mut(parent) {
+                cs.0.retain(|e| *e != child);
+            }
+        }
+    }
+}
+
+/// Makes `child` a child of `parent`, removing it from the previous parent's children
+pub(crate) fn add_child(world: &mut World, parent: Entity, child: Entity) {
+    world.register::<Parent>();
+    world.register::<Children>();
+
+    self::detach(world, child);
+
+    let mut parents = world.comp_mut::<Parent>();
+    parents.insert(child, Parent(parent));
+
+    let mut children = world.comp_mut::<Children>();
+    match children.get_mut(parent) {
+        Some(cs) => {
+            if !cs.0.contains(&child) {
+                cs.0.push(child);
+            }
+        }
+        None => {
+            children.insert(parent, Children(vec![child]));
+        }
+    }
+}
+
+/// Removes the parent/child relationship. Returns false if they're not parent and child.
+pub(crate) fn remove_child(world: &mut World, parent: Entity, child: Entity) -> bool {
+    let is_child = match world.try_comp::<Parent>() {
+        Ok(parents) => parents.get(child) == Some(&Parent(parent)),
+        Err(_) => false,
+    };
+
+    if !is_child {
+        return false;
+    }
+
+    self::detach(world, child);
+    world.remove::<Parent>(child);
+    true
+}
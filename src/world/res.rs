@@ -1,12 +1,42 @@
 //! Resources: virtually `World` fields backed by an anymap
 //!
+//! # Trait object resources
+//!
+//! `Box<dyn Trait>` is a resource as long as the trait object is `Debug`. It's keyed by the
+//! `TypeId` of the box, so borrow it as `Res<Box<dyn Trait>>`:
+//!
+//! ```
+//! use std::fmt;
+//! use toecs::prelude::*;
+//!
+//! trait Shape: fmt::Debug {
+//!     fn area(&self) -> f32;
+//! }
+//!
+//! #[derive(Debug)]
+//! struct Square(f32);
+//!
+//! impl Shape for Square {
+//!     fn area(&self) -> f32 {
+//!         self.0 * self.0
+//!     }
+//! }
+//!
+//! let mut world = World::default();
+//! world.set_res::<Box<dyn Shape>>(Box::new(Square(2.0)));
+//! assert_eq!(world.res::<Box<dyn Shape>>().area(), 4.0);
+//! ```
+//!
 //! TODO: Separate non-sync/non-send resources
 
 use std::{
     any::{self, TypeId},
     borrow,
     cell::RefCell,
-    fmt, mem, ops,
+    fmt,
+    marker::PhantomData,
+    mem, ops,
+    panic::Location,
 };
 
 use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
@@ -14,6 +44,8 @@ use downcast_rs::{impl_downcast, Downcast};
 use rustc_hash::FxHashMap;
 use thiserror::Error;
 
+use crate::world::borrow::BorrowTracker;
+
 /// Type boundary for resource types
 pub trait Resource: 'static + fmt::Debug + Downcast {}
 
@@ -23,17 +55,50 @@ impl<T: 'static + fmt::Debug> Resource for T {}
 
 /// Resource fetch error type
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum BorrowError {
     #[error("resource of type `{0}` is not set")]
     NotFound(&'static str),
     #[error("resource of type `{0}` is already borrowed")]
     AlreadyBorrowed(&'static str),
+    /// `AlreadyBorrowed` with the location of the last borrow. It's only returned with
+    /// `debug-borrow-tracking` feature.
+    #[error("resource of type `{0}` is already borrowed (last borrowed at {1})")]
+    AlreadyBorrowedAt(&'static str, &'static Location<'static>),
+}
+
+impl BorrowError {
+    fn already_borrowed(ty: &'static str, loc: Option<&'static Location<'static>>) -> Self {
+        match loc {
+            Some(loc) => Self::AlreadyBorrowedAt(ty, loc),
+            None => Self::AlreadyBorrowed(ty),
+        }
+    }
+
+    /// Returns the location of the last borrow. It's always `None` without
+    /// `debug-borrow-tracking` feature.
+    pub fn location(&self) -> Option<&'static Location<'static>> {
+        match self {
+            Self::AlreadyBorrowedAt(_, loc) => Some(loc),
+            _ => None,
+        }
+    }
+}
+
+/// How to handle resources present in both worlds on [`World::merge`](crate::World::merge)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keeps the resources of the destination world
+    Keep,
+    /// Overwrites with the resources of the merged world
+    Overwrite,
 }
 
 /// Dynamic fields of a `World` backed by an anymap
 #[derive(Debug, Default)]
 pub struct ResourceMap {
     cells: FxHashMap<TypeId, AtomicRefCell<AnyResource>>,
+    tracker: BorrowTracker,
 }
 
 #[derive(Debug)]
@@ -68,16 +133,73 @@ impl ResourceMap {
         self.cells.contains_key(&TypeId::of::<T>())
     }
 
+    /// [`contains`] by `TypeId`
+    ///
+    /// [`contains`]: Self::contains
+    pub fn contains_raw(&self, ty: TypeId) -> bool {
+        self.cells.contains_key(&ty)
+    }
+
+    /// Returns the type name of a resource. This is safe because it has exclusive access.
+    pub(crate) fn type_name_raw(&mut self, ty: TypeId) -> Option<&'static str> {
+        Some(self.cells.get_mut(&ty)?.get_mut().of_type)
+    }
+
+    /// Gets a mutable reference to a resource. This is safe because it has exclusive access.
+    pub fn get_mut<T: Resource>(&mut self) -> Option<&mut T> {
+        let cell = self.cells.get_mut(&TypeId::of::<T>())?;
+        let res = cell.get_mut().any.downcast_mut::<T>().unwrap();
+        Some(res)
+    }
+
+    /// Gets a mutable reference to a resource, inserting the value made with `f` if it's absent.
+    /// This is safe because it has exclusive access.
+    pub fn get_or_insert_with<T: Resource>(&mut self, f: impl FnOnce() -> T) -> &mut T {
+        self.entry::<T>().or_insert_with(f)
+    }
+
+    /// Gets an insert-or-modify access to a resource of type `T`
+    pub fn entry<T: Resource>(&mut self) -> ResEntry<T> {
+        ResEntry {
+            res: self,
+            _ty: PhantomData,
+        }
+    }
+
+    /// Moves the resources of another map into this map
+    pub fn merge(&mut self, other: ResourceMap, policy: MergePolicy) {
+        for (ty, cell) in other.cells {
+            match policy {
+                MergePolicy::Keep => {
+                    self.cells.entry(ty).or_insert(cell);
+                }
+                MergePolicy::Overwrite => {
+                    self.cells.insert(ty, cell);
+                }
+            }
+        }
+    }
+
+    /// Enumerates the types of the resources currently set
+    pub fn iter_types(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.cells.keys().copied()
+    }
+
     /// Tries to get an immutable access to a resource
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn try_borrow<T: Resource>(&self) -> Result<Res<T>, BorrowError> {
         let cell = self
             .cells
             .get(&TypeId::of::<T>())
             .ok_or_else(|| BorrowError::NotFound(any::type_name::<T>()))?;
 
-        let inner = cell
-            .try_borrow()
-            .map_err(|_| BorrowError::AlreadyBorrowed(any::type_name::<T>()))?;
+        let inner = cell.try_borrow().map_err(|_| {
+            BorrowError::already_borrowed(
+                any::type_name::<T>(),
+                self.tracker.location(TypeId::of::<T>()),
+            )
+        })?;
+        self.tracker.record(TypeId::of::<T>());
 
         let borrow = AtomicRef::map(inner, |res| {
             res.any
@@ -89,15 +211,20 @@ impl ResourceMap {
     }
 
     /// Tries to get a mutable access to a resource
+    #[cfg_attr(feature = "debug-borrow-tracking", track_caller)]
     pub fn try_borrow_mut<T: Resource>(&self) -> Result<ResMut<T>, BorrowError> {
         let cell = self
             .cells
             .get(&TypeId::of::<T>())
             .ok_or_else(|| BorrowError::NotFound(any::type_name::<T>()))?;
 
-        let inner = cell
-            .try_borrow_mut()
-            .map_err(|_| BorrowError::AlreadyBorrowed(any::type_name::<T>()))?;
+        let inner = cell.try_borrow_mut().map_err(|_| {
+            BorrowError::already_borrowed(
+                any::type_name::<T>(),
+                self.tracker.location(TypeId::of::<T>()),
+            )
+        })?;
+        self.tracker.record(TypeId::of::<T>());
 
         let borrow = AtomicRefMut::map(inner, |res| {
             res.any
@@ -108,6 +235,20 @@ impl ResourceMap {
         Ok(ResMut { borrow })
     }
 
+    /// Enumerates the resources. Downcast them to access the concrete types.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut dyn Resource> {
+        self.cells.values_mut().map(|cell| &mut *cell.get_mut().any)
+    }
+
+    /// Enumerates type names and values of the resources. This is safe because it has exclusive
+    /// access.
+    pub(crate) fn iter_debug(&mut self) -> impl Iterator<Item = (&'static str, &dyn fmt::Debug)> {
+        self.cells.values_mut().map(|cell| {
+            let res = cell.get_mut();
+            (res.of_type, &res.any as &dyn fmt::Debug)
+        })
+    }
+
     /// Returns a debug display. This is safe because it has exclusive access.
     pub fn display(&mut self) -> ResourceMapDisplay {
         let mut res = Self::default();
@@ -119,6 +260,35 @@ impl ResourceMap {
     }
 }
 
+/// Insert-or-modify access to a resource. See [`ResourceMap::entry`].
+pub struct ResEntry<'r, T: Resource> {
+    res: &'r mut ResourceMap,
+    _ty: PhantomData<T>,
+}
+
+impl<'r, T: Resource> ResEntry<'r, T> {
+    /// Modifies the resource if it's present
+    pub fn and_modify(self, f: impl FnOnce(&mut T)) -> Self {
+        if let Some(res) = self.res.get_mut::<T>() {
+            f(res);
+        }
+        self
+    }
+
+    /// Inserts the default value if the resource is not present
+    pub fn or_insert(self, default: T) -> &'r mut T {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the value made with `f` if the resource is not present
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> &'r mut T {
+        if !self.res.contains::<T>() {
+            self.res.insert(f());
+        }
+        self.res.get_mut::<T>().unwrap()
+    }
+}
+
 /// See [`ResourceMap::display`]
 pub struct ResourceMapDisplay<'r> {
     res: RefCell<ResourceMap>,
@@ -159,6 +329,18 @@ impl<'r, T: Resource> ops::Deref for Res<'r, T> {
     }
 }
 
+impl<'r, T: Resource + PartialEq> PartialEq<T> for Res<'r, T> {
+    fn eq(&self, other: &T) -> bool {
+        ops::Deref::deref(&self.borrow) == other
+    }
+}
+
+impl<'r, T: Resource + fmt::Display> fmt::Display for Res<'r, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(ops::Deref::deref(&self.borrow), f)
+    }
+}
+
 impl<'r, T: Resource> borrow::Borrow<T> for Res<'r, T> {
     fn borrow(&self) -> &T {
         ops::Deref::deref(&self.borrow)
@@ -170,6 +352,23 @@ impl<'r, T: Resource> Res<'r, T> {
     pub fn deref(&self) -> &T {
         ops::Deref::deref(self)
     }
+
+    /// Projects the borrow into a part of the resource, such as a field. It's an associated
+    /// function so that it doesn't shadow the methods of `T`.
+    pub fn map<U: Resource>(orig: Self, f: impl FnOnce(&T) -> &U) -> Res<'r, U> {
+        Res {
+            borrow: AtomicRef::map(orig.borrow, f),
+        }
+    }
+
+    /// Projects the borrow into a part of the resource if `f` returns some reference
+    pub fn filter_map<U: Resource>(
+        orig: Self,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> Option<Res<'r, U>> {
+        let borrow = AtomicRef::filter_map(orig.borrow, f)?;
+        Some(Res { borrow })
+    }
 }
 
 /// Mutable access to a resource of type `T`
@@ -193,6 +392,18 @@ impl<'r, T: Resource> ops::DerefMut for ResMut<'r, T> {
     }
 }
 
+impl<'r, T: Resource + PartialEq> PartialEq<T> for ResMut<'r, T> {
+    fn eq(&self, other: &T) -> bool {
+        ops::Deref::deref(&self.borrow) == other
+    }
+}
+
+impl<'r, T: Resource + fmt::Display> fmt::Display for ResMut<'r, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(ops::Deref::deref(&self.borrow), f)
+    }
+}
+
 impl<'r, T: Resource> borrow::Borrow<T> for ResMut<'r, T> {
     fn borrow(&self) -> &T {
         ops::Deref::deref(&self.borrow)
@@ -215,4 +426,21 @@ impl<'r, T: Resource> ResMut<'r, T> {
     pub fn deref_mut(&mut self) -> &mut T {
         ops::DerefMut::deref_mut(self)
     }
+
+    /// Projects the borrow into a part of the resource, such as a field. It's an associated
+    /// function so that it doesn't shadow the methods of `T`.
+    pub fn map<U: Resource>(orig: Self, f: impl FnOnce(&mut T) -> &mut U) -> ResMut<'r, U> {
+        ResMut {
+            borrow: AtomicRefMut::map(orig.borrow, f),
+        }
+    }
+
+    /// Projects the borrow into a part of the resource if `f` returns some reference
+    pub fn filter_map<U: Resource>(
+        orig: Self,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Option<ResMut<'r, U>> {
+        let borrow = AtomicRefMut::filter_map(orig.borrow, f)?;
+        Some(ResMut { borrow })
+    }
 }
@@ -2,7 +2,9 @@
 //!
 //! This module is public, but just for the intenral documentation. See also `EntityPool` as a sparse
 
-use std::{iter, num::NonZeroU32, slice};
+use std::{fmt, iter, mem, num::NonZeroU32, slice};
+
+use thiserror::Error;
 
 /// The length of [`SparseArray`] will be multiples of this value
 const UNIT_LEN: usize = 64;
@@ -17,7 +19,7 @@ macro_rules! newtype_index {
         impl $ty {
             pub const ZERO: Self = Self(0);
 
-            pub(crate) fn from_usize(x: usize) -> Self {
+            pub fn from_usize(x: usize) -> Self {
                 Self(x as $internal)
             }
 
@@ -30,7 +32,7 @@ macro_rules! newtype_index {
 
 newtype_index! {
     /// Newtype sparse index
-    pub(crate) RawSparseIndex(u32);
+    pub RawSparseIndex(u32);
 }
 
 newtype_index! {
@@ -57,29 +59,54 @@ impl Generation {
         raw: unsafe { NonZeroU32::new_unchecked(1) },
     };
 
+    /// The last generation, which is never reached in practice
+    pub(crate) const MAX: Generation = Self {
+        raw: NonZeroU32::MAX,
+    };
+
+    /// Creates a generation from the raw value. Returns `None` for zero, which is not a valid
+    /// generation.
+    pub fn new(raw: u32) -> Option<Self> {
+        NonZeroU32::new(raw).map(|raw| Self { raw })
+    }
+
+    /// # Panics
+    /// Panics on overflow.
     pub(crate) fn increment(self) -> Self {
         Self {
-            raw: unsafe { NonZeroU32::new_unchecked(self.raw.get() + 1) },
+            raw: self.raw.checked_add(1).expect("generation overflow"),
         }
     }
 
+    /// Returns the raw value, which is never zero
+    pub fn value(&self) -> u32 {
+        self.raw.get()
+    }
+
     pub fn to_usize(&self) -> usize {
         self.raw.get() as usize
     }
 }
 
+impl fmt::Display for Generation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
 macro_rules! generational_index {
     ($(#[$meta:meta])* $vis:vis $ty:ident($index:ty);) => {
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
         $(#[$meta])*
         $vis struct $ty {
+            // NOTE: field order matters for the derived `Ord` (slot first, then generation)
             raw: $index,
             gen: Generation,
         }
 
         #[allow(unused)]
         impl $ty {
-            pub(crate) fn new(raw: $index, gen: Generation) -> Self {
+            pub(crate) const fn new(raw: $index, gen: Generation) -> Self {
                 Self {
                     raw,
                     gen,
@@ -178,6 +205,12 @@ pub struct SparseSet<T> {
 Sparse set is intended for Struct of Arrays. Ideally, all relevant dense vecs should be accessed
 with the same dense index, which is called "perfect SoA". It requires syncing and sorting. There's a
 known workaround called "groups".
+
+# Generations
+
+Each slot remembers the generation of the index it was last inserted with. Lookup with a different
+generation just returns `None`, but insertion with an older generation (a stale index) is a bug. See
+[`insert_checked`](Self::insert_checked).
 */
 #[derive(Debug, Clone)]
 pub struct SparseSet<T> {
@@ -202,11 +235,40 @@ impl<T> Default for SparseSet<T> {
 }
 
 impl<T> SparseSet<T> {
+    /// Creates a sparse set with the dense arrays reserving the capacity
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            to_dense: Default::default(),
+            to_sparse: Vec::with_capacity(cap),
+            data: Vec::with_capacity(cap),
+        }
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.data.len()
     }
 
+    /// Returns the capacity of the dense arrays
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Returns the heap memory allocated by the set in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.to_dense.data.capacity() * mem::size_of::<Option<DenseIndex>>()
+            + self.to_sparse.capacity() * mem::size_of::<SparseIndex>()
+            + self.data.capacity() * mem::size_of::<T>()
+    }
+
+    /// Shrinks the capacity of the dense arrays and the sparse array as much as possible
+    pub fn shrink_to_fit(&mut self) {
+        self.to_dense.shrink_to_fit();
+        self.to_sparse.shrink_to_fit();
+        self.data.shrink_to_fit();
+    }
+
     pub fn as_slice(&self) -> &[T] {
         &self.data
     }
@@ -235,6 +297,18 @@ impl<T> SparseSet<T> {
         self.to_sparse.iter().zip(self.data.iter())
     }
 
+    /// Returns an iterator of indices and mutable references to the items
+    pub fn iter_mut_with_index(
+        &mut self,
+    ) -> iter::Zip<iter::Copied<slice::Iter<SparseIndex>>, slice::IterMut<T>> {
+        self.to_sparse.iter().copied().zip(self.data.iter_mut())
+    }
+
+    /// Consumes the set and returns the dense arrays of indices and data
+    pub fn into_dense(self) -> (Vec<SparseIndex>, Vec<T>) {
+        (self.to_sparse, self.data)
+    }
+
     pub fn contains(&self, sparse: SparseIndex) -> bool {
         let dense = match self.to_dense.get(sparse) {
             Some(dense) => dense,
@@ -302,13 +376,34 @@ impl<T> SparseSet<T> {
     }
 
     /// Returns old item if it's present
-    pub fn insert(&mut self, sparse: SparseIndex, mut data: T) -> Option<T> {
+    ///
+    /// # Panics
+    /// Panics on debug build if the index is stale. See [`insert_checked`](Self::insert_checked).
+    pub fn insert(&mut self, sparse: SparseIndex, data: T) -> Option<T> {
+        if cfg!(debug_assertions) {
+            self.insert_checked(sparse, data)
+                .unwrap_or_else(|err| panic!("{}", err))
+        } else {
+            self.insert_unchecked(sparse, data)
+        }
+    }
+
+    /// Returns old item if it's present. Returns `Err` without modifying the set if the index is
+    /// older than the one in the set.
+    pub fn insert_checked(
+        &mut self,
+        sparse: SparseIndex,
+        data: T,
+    ) -> Result<Option<T>, StaleIndexError> {
+        if let Some(dense) = self.to_dense.get(sparse) {
+            self::check_generation(sparse, dense)?;
+        }
+        Ok(self.insert_unchecked(sparse, data))
+    }
+
+    fn insert_unchecked(&mut self, sparse: SparseIndex, mut data: T) -> Option<T> {
         match self.to_dense.get_or_alloc_mut(sparse) {
             Some(dense) => {
-                debug_assert!(
-                    sparse.gen >= dense.gen,
-                    "generation has to increase monotonically"
-                );
                 // overwrite the existing slots
                 dense.gen = sparse.gen;
                 std::mem::swap(&mut self.data[dense.to_usize()], &mut data);
@@ -335,18 +430,24 @@ impl<T> SparseSet<T> {
     }
 
     pub fn swap_remove(&mut self, sparse: SparseIndex) -> Option<T> {
-        let dense = self.to_dense.remove(sparse)?;
-        debug_assert!(sparse.gen <= dense.gen);
+        self.swap_remove_tracked(sparse).map(|(removal, _)| removal)
+    }
 
+    /// Swap-removes an item, returning the item and the index of the item moved into the vacated
+    /// dense slot, if any
+    pub fn swap_remove_tracked(&mut self, sparse: SparseIndex) -> Option<(T, Option<SparseIndex>)> {
+        let dense = self.to_dense.get(sparse)?;
         if dense.gen != sparse.gen {
             return None;
         }
+        self.to_dense.remove(sparse);
 
         let removal = self.data.swap_remove(dense.to_usize());
         self.to_sparse.swap_remove(dense.to_usize());
 
         // if we swap the last item with the hole
-        if let Some(swapped_sparse) = self.to_sparse.get(dense.to_usize()) {
+        let swapped = self.to_sparse.get(dense.to_usize()).copied();
+        if let Some(swapped_sparse) = swapped {
             // update the sparse-dense map to the swapped item
             self.to_dense.set(
                 swapped_sparse.to_usize(),
@@ -357,7 +458,73 @@ impl<T> SparseSet<T> {
             );
         }
 
-        Some(removal)
+        Some((removal, swapped))
+    }
+
+    /// Removes the sparse-to-dense mapping of an item, leaving it in the dense arrays. Returns the
+    /// dense index of the unmapped item.
+    pub fn unmap(&mut self, sparse: SparseIndex) -> Option<DenseIndex> {
+        let dense = self.to_dense.get(sparse)?;
+        if dense.gen != sparse.gen {
+            return None;
+        }
+        self.to_dense.remove(sparse)
+    }
+
+    /// Returns true if the item at the dense slot is not mapped from the sparse array
+    pub fn is_unmapped(&self, slot: usize) -> bool {
+        let sparse = self.to_sparse[slot];
+        self.to_dense
+            .get(sparse)
+            .is_none_or(|dense| dense.to_usize() != slot)
+    }
+
+    /// Removes the unmapped items from the dense arrays, keeping the order of the other items
+    pub fn remove_unmapped(&mut self) {
+        let keep = (0..self.data.len())
+            .map(|slot| !self.is_unmapped(slot))
+            .collect::<Vec<_>>();
+
+        let mut keep_data = keep.iter();
+        self.data.retain(|_| *keep_data.next().unwrap());
+        let mut keep_sparse = keep.iter();
+        self.to_sparse.retain(|_| *keep_sparse.next().unwrap());
+
+        for (i, sparse) in self.to_sparse.iter().enumerate() {
+            self.to_dense.set(
+                sparse.to_usize(),
+                DenseIndex {
+                    raw: RawDenseIndex::from_usize(i),
+                    gen: sparse.gen,
+                },
+            );
+        }
+    }
+
+    /// Sorts the dense arrays, keeping the sparse-to-dense map in sync
+    pub fn sort_by_key<K: Ord>(&mut self, mut f: impl FnMut(&T) -> K) {
+        let mut order = (0..self.data.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| f(&self.data[i]));
+
+        let mut data = std::mem::take(&mut self.data)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.data = order
+            .iter()
+            .map(|&i| data[i].take().unwrap_or_else(|| unreachable!()))
+            .collect();
+        self.to_sparse = order.iter().map(|&i| self.to_sparse[i]).collect();
+
+        for (i, sparse) in self.to_sparse.iter().enumerate() {
+            self.to_dense.set(
+                sparse.to_usize(),
+                DenseIndex {
+                    raw: RawDenseIndex::from_usize(i),
+                    gen: sparse.gen,
+                },
+            );
+        }
     }
 
     pub fn parts(&self) -> (&[Option<DenseIndex>], &[SparseIndex], &[T]) {
@@ -369,6 +536,33 @@ impl<T> SparseSet<T> {
     }
 }
 
+/// Error on inserting an item with a stale index
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error(
+    "stale index at slot {}: generation {} is older than {}",
+    slot,
+    given.to_usize(),
+    stored.to_usize()
+)]
+pub struct StaleIndexError {
+    pub slot: usize,
+    pub given: Generation,
+    pub stored: Generation,
+}
+
+/// The generation invariant: generation of a slot has to increase monotonically
+fn check_generation(sparse: SparseIndex, dense: DenseIndex) -> Result<(), StaleIndexError> {
+    if sparse.gen >= dense.gen {
+        Ok(())
+    } else {
+        Err(StaleIndexError {
+            slot: sparse.to_usize(),
+            given: sparse.gen,
+            stored: dense.gen,
+        })
+    }
+}
+
 /// Maps [`SparseIndex`] to [`DenseIndex`]
 #[derive(Debug, Clone)]
 struct SparseArray {
@@ -386,13 +580,7 @@ impl Default for SparseArray {
 impl SparseArray {
     /// Returns the corresponding item's slot
     pub fn get(&self, sparse: SparseIndex) -> Option<DenseIndex> {
-        self.data.get(sparse.to_usize())?.map(|dense| {
-            debug_assert!(
-                sparse.gen <= dense.gen,
-                "generation has to increase monotonically"
-            );
-            dense.clone()
-        })
+        *self.data.get(sparse.to_usize())?
     }
 
     pub fn set(&mut self, sparse_slot: usize, dense: DenseIndex) {
@@ -411,6 +599,17 @@ impl SparseArray {
         self.data.get_mut(idx.to_usize())?.take()
     }
 
+    /// Truncates the trailing empty slots and shrinks the capacity
+    pub fn shrink_to_fit(&mut self) {
+        let len = self
+            .data
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |slot| slot + 1);
+        self.data.truncate(len);
+        self.data.shrink_to_fit();
+    }
+
     /// After `grow`, `self.data.len() >= target_slot + 1`
     fn maybe_grow(&mut self, target_slot: usize) -> bool {
         if self.data.len() >= target_slot + 1 {
@@ -1,10 +1,13 @@
+use std::{any::TypeId, collections::HashMap};
+
 use crate::{
+    query::Iter,
     sys::System,
     world::{
-        comp::{Comp, CompMut, Component, ComponentPoolMap},
+        comp::{Comp, CompMut, Component, ComponentPool, ComponentPoolMap},
         ent::{Entity, EntityPool},
-        res::{Res, ResMut, ResourceMap},
-        sparse::{RawSparseIndex, SparseIndex},
+        res::{self, Res, ResMut, ResourceMap},
+        sparse::{DenseIndex, Generation, RawSparseIndex, SparseIndex},
         ComponentSet, World,
     },
 };
@@ -31,11 +34,30 @@ fn resource_map() {
         assert_eq!(i.0, -30 + 5);
     }
 
+    let mut types = res.iter_types().collect::<Vec<_>>();
+    types.sort();
+    let mut expected = vec![TypeId::of::<U>(), TypeId::of::<I>()];
+    expected.sort();
+    assert_eq!(types, expected);
+
     // insert, remove
     assert_eq!(res.insert(U(2)), Some(U(30 + 5)));
     assert_eq!(res.remove::<U>(), Some(U(2)));
 }
 
+#[test]
+fn resource_get_or_insert() {
+    let mut res = ResourceMap::default();
+
+    // miss
+    res.get_or_insert_with(|| U(10)).0 += 1;
+    assert_eq!(*res.try_borrow::<U>().unwrap(), U(11));
+
+    // hit
+    let u = res.get_or_insert_with::<U>(|| unreachable!());
+    assert_eq!(*u, U(11));
+}
+
 #[test]
 #[should_panic]
 fn resource_panic() {
@@ -97,6 +119,17 @@ fn sparse_set() {
     assert_eq!(set.get(i1_new), Some(&100));
     assert_eq!(set.get(i2), Some(&2));
 
+    // stale index can't overwrite the slot
+    assert_eq!(
+        set.insert_checked(i1, 1),
+        Err(StaleIndexError {
+            slot: 1,
+            given: i1.generation(),
+            stored: i1_new.generation(),
+        })
+    );
+    assert_eq!(set.get(i1_new), Some(&100));
+
     assert_eq!(set.swap_remove(i0), Some(0));
 
     for (i, x) in set.iter_with_index() {
@@ -132,6 +165,217 @@ fn entity_pool() {
     assert_eq!(pool.iter().collect::<Vec<_>>(), [&e0, &e2_new]);
 }
 
+#[test]
+fn entity_alloc_at() {
+    let mut pool = EntityPool::default();
+    let e0 = pool.alloc();
+
+    let slot0 = RawSparseIndex::from_usize(0);
+    let slot3 = RawSparseIndex::from_usize(3);
+    let gen = Generation::INITIAL.increment().increment();
+
+    // the slot is alive
+    assert_eq!(pool.alloc_at(slot0, gen), None);
+
+    // allocate beyond the end
+    let e3 = pool.alloc_at(slot3, gen).unwrap();
+    assert!(pool.contains(e0) && pool.contains(e3));
+    assert_eq!(e3.generation(), gen);
+    assert_eq!(pool.iter().collect::<Vec<_>>(), [&e0, &e3]);
+
+    // the gap is recycled
+    let e1 = pool.alloc();
+    let e2 = pool.alloc();
+    let mut slots = [e1.0.to_usize(), e2.0.to_usize()];
+    slots.sort();
+    assert_eq!(slots, [1, 2]);
+    assert_eq!(e1.generation(), Generation::INITIAL);
+
+    // generation has to advance
+    assert!(pool.dealloc(e3));
+    assert_eq!(pool.alloc_at(slot3, gen), None);
+    let e3_new = pool.alloc_at(slot3, gen.increment()).unwrap();
+    assert!(!pool.contains(e3) && pool.contains(e3_new));
+
+    // allocate at a free slot in the middle of the free list
+    assert!(pool.dealloc(e1));
+    assert!(pool.dealloc(e2));
+    let e1_new = pool
+        .alloc_at(e1.0.raw(), e1.generation().increment())
+        .unwrap();
+    assert!(pool.contains(e1_new));
+    let e2_new = pool.alloc();
+    assert_eq!(e2_new.0.raw(), e2.0.raw());
+    assert_eq!(pool.alloc().0.to_usize(), 4);
+
+    // replay out of order at the initial generation
+    let slot1 = RawSparseIndex::from_usize(1);
+    let mut replay = EntityPool::default();
+    let r1 = replay.alloc_at(slot1, Generation::INITIAL).unwrap();
+    let r0 = replay.alloc_at(slot0, Generation::INITIAL).unwrap();
+    assert!(replay.contains(r0) && replay.contains(r1));
+    assert_eq!(r0, e0);
+
+    // the last generation is reserved
+    let max = Generation::new(u32::MAX).unwrap();
+    assert_eq!(replay.alloc_at(RawSparseIndex::from_usize(2), max), None);
+}
+
+#[test]
+fn entity_placeholder() {
+    assert_eq!(
+        std::mem::size_of::<Entity>(),
+        std::mem::size_of::<Option<Entity>>()
+    );
+
+    let mut world = World::default();
+    world.register::<U>();
+    let e0 = world.spawn(U(0));
+    let e1 = world.spawn(U(1));
+    assert_eq!((e0.index(), e1.index()), (0, 1));
+
+    assert_ne!(Entity::PLACEHOLDER, e0);
+    assert!(!world.contains(Entity::PLACEHOLDER));
+    assert!(world.comp::<U>().get(Entity::PLACEHOLDER).is_none());
+    assert!(!world.despawn(Entity::PLACEHOLDER));
+}
+
+#[test]
+fn entity_ord() {
+    use std::collections::BTreeSet;
+
+    let mut world = World::default();
+    let e0 = world.spawn_empty();
+    let e1 = world.spawn_empty();
+    world.despawn(e0);
+    // recycles slot 0 with a newer generation
+    let e2 = world.spawn_empty();
+    assert_eq!(e2.index(), 0);
+
+    let mut ents = vec![e1, e2, e0];
+    ents.sort();
+    assert_eq!(ents, [e0, e2, e1]);
+
+    let set = [e1, e2, e0, e2].into_iter().collect::<BTreeSet<_>>();
+    assert_eq!(set.into_iter().collect::<Vec<_>>(), [e0, e2, e1]);
+}
+
+#[test]
+fn generation() {
+    assert_eq!(Generation::new(0), None);
+    assert_eq!(Generation::new(1), Some(Generation::INITIAL));
+
+    let g2 = Generation::new(2).unwrap();
+    assert_eq!(g2.value(), 2);
+    assert_eq!(g2.to_string(), "2");
+    assert!(Generation::INITIAL < g2);
+    assert_eq!(Generation::INITIAL.increment(), g2);
+}
+
+#[test]
+#[should_panic(expected = "generation overflow")]
+fn generation_overflow() {
+    let max = Generation::new(u32::MAX).unwrap();
+    max.increment();
+}
+
+#[test]
+fn entity_capacity() {
+    let mut world = World::default();
+    world.spawn_empty();
+
+    world.reserve_entity_capacity(1000);
+    let cap = world.ents.capacity();
+    assert!(cap >= 1001);
+
+    for _ in 0..1000 {
+        world.reserve_atomic();
+    }
+    world.synchronize();
+    assert_eq!(world.entities().len(), 1001);
+    assert_eq!(world.ents.capacity(), cap);
+
+    // `synchronize` allocates once
+    let mut pool = EntityPool::default();
+    for _ in 0..1000 {
+        pool.reserve_atomic();
+    }
+    pool.synchronize();
+    assert_eq!(pool.capacity(), 1000);
+}
+
+#[test]
+fn entity_pool_clear() {
+    let mut pool = EntityPool::default();
+    let e0 = pool.alloc();
+    let e1 = pool.alloc();
+    pool.dealloc(e0);
+    pool.reserve_atomic();
+
+    pool.clear();
+    assert!(!pool.contains(e1));
+    assert_eq!(pool.slice().len(), 0);
+
+    // the generation history is discarded
+    let e = pool.alloc();
+    assert_eq!(e, e0);
+    assert_eq!(e.generation(), Generation::INITIAL);
+    assert_eq!(pool.alloc(), e1);
+}
+
+#[test]
+fn stale_entity() {
+    let mut world = World::default();
+    let e0 = world.spawn_empty();
+    assert_eq!(world.is_stale(e0), None);
+
+    // dead, but the slot is not reused yet
+    world.despawn(e0);
+    assert_eq!(world.ents.generation_of(e0.0.raw()), None);
+    assert_eq!(world.is_stale(e0), None);
+
+    // the slot is reused
+    let e1 = world.spawn_empty();
+    assert_eq!(e1.0.raw(), e0.0.raw());
+    assert_eq!(world.ents.generation_of(e0.0.raw()), Some(e1.generation()));
+    assert_eq!(world.is_stale(e0), Some(e1.generation()));
+    assert_eq!(world.is_stale(e1), None);
+}
+
+#[test]
+fn stale_entity_query() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let e0 = world.spawn(U(0));
+    world.despawn(e0);
+    let e1 = world.spawn(U(1));
+    assert_eq!(e1.0.raw(), e0.0.raw());
+
+    // queries with the stale entity return `None` without panicking (even on debug build)
+    let mut us = world.comp_mut::<U>();
+    assert!(!us.contains(e0));
+    assert_eq!(us.get(e0), None);
+    assert_eq!(us.get_mut(e0), None);
+    assert_eq!(us.swap_remove(e0), None);
+    assert_eq!(us.get(e1), Some(&U(1)));
+}
+
+#[test]
+fn register_and_borrow_mut() {
+    let mut world = World::default();
+    let e0 = world.spawn_empty();
+    let e1 = world.spawn_empty();
+
+    world.register_and_borrow_mut::<U>().insert(e0, U(0));
+    assert!(world.is_registered::<U>());
+
+    // already registered pools are just borrowed
+    let mut us = world.register_and_borrow_mut::<U>();
+    us.insert(e1, U(1));
+    assert_eq!(us.as_slice(), [U(0), U(1)]);
+}
+
 #[test]
 fn component_pool_map() {
     let mut world = World::default();
@@ -159,6 +403,335 @@ fn component_pool_map() {
     assert_eq!(is.swap_remove(e0), Some(I(-0)));
     assert_eq!(is.get(e1), Some(&I(-1)));
     assert_eq!(is.get(e2), Some(&I(-2)));
+
+    let (ents, comps): (Vec<Entity>, Vec<&U>) = us.iter_dense().unzip();
+    assert_eq!(ents, us.entities());
+    assert_eq!(comps, us.as_slice().iter().collect::<Vec<_>>());
+
+    let map = us.iter_dense().collect::<HashMap<_, _>>();
+    assert_eq!(map.len(), 3);
+    assert_eq!(map[&e0], &U(0));
+    assert_eq!(map[&e2], &U(2));
+}
+
+#[test]
+fn sorted_component_pool() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let ents = [30, 10, 50, 20, 40].map(|x| world.spawn(U(x)));
+
+    let mut us = world.comp_mut::<U>();
+    us.sort_by_key(|u| u.0);
+    assert_eq!(us.as_slice(), [U(10), U(20), U(30), U(40), U(50)]);
+
+    // entities are kept in sync
+    for (ent, x) in ents.iter().zip([30, 10, 50, 20, 40]) {
+        assert_eq!(us.get(*ent), Some(&U(x)));
+    }
+
+    let slot = us.binary_search_by_key(&40, |u| u.0).unwrap();
+    assert_eq!(us.entities()[slot], ents[4]);
+    assert_eq!(us.binary_search_by_key(&35, |u| u.0), Err(3));
+
+    // map dense slots back to the entities
+    let owners = (0..us.len()).map(|slot| us.entity_at(slot).unwrap());
+    assert!(owners.eq([ents[1], ents[3], ents[0], ents[4], ents[2]]));
+    assert_eq!(us.entity_at(us.len()), None);
+}
+
+#[test]
+fn swap_remove_tracked() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let ents = [0, 1, 2, 3].map(|x| world.spawn(U(x)));
+
+    // a parallel array that follows the dense slots
+    let mut us = world.comp_mut::<U>();
+    let mut labels = vec!["a", "b", "c", "d"];
+
+    let last = us.entity_at(us.len() - 1);
+    let (u, swapped) = us.swap_remove_tracked(ents[1]).unwrap();
+    assert_eq!((u, swapped), (U(1), last));
+    assert_eq!(swapped, Some(ents[3]));
+    labels.swap_remove(1);
+    assert_eq!(us.entity_at(1), swapped);
+    assert_eq!(labels[1], "d");
+
+    // removing the last component moves nothing
+    assert_eq!(us.swap_remove_tracked(ents[2]), Some((U(2), None)));
+    assert_eq!(us.swap_remove_tracked(ents[2]), None);
+    assert_eq!(us.as_slice(), [U(0), U(3)]);
+    assert_eq!(us.get(ents[3]), Some(&U(3)));
+}
+
+#[test]
+fn component_pool_chunks() {
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct F(f32);
+
+    let mut world = World::default();
+    world.register::<F>();
+    for i in 0..10 {
+        world.spawn(F(i as f32));
+    }
+
+    let mut fs = world.comp_mut::<F>();
+    for chunk in fs.chunks_mut(4) {
+        chunk.iter_mut().for_each(|f| f.0 *= 2.0);
+    }
+
+    // sums up in lanes like SIMD
+    let mut lanes = [0.0f32; 4];
+    for chunk in fs.chunks(4) {
+        for (lane, f) in lanes.iter_mut().zip(chunk) {
+            *lane += f.0;
+        }
+    }
+    assert_eq!(lanes.iter().sum::<f32>(), 2.0 * 45.0);
+    assert_eq!(fs.chunks(4).map(|c| c.len()).collect::<Vec<_>>(), [4, 4, 2]);
+}
+
+#[test]
+fn filter_present() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    let e0 = world.spawn(U(0));
+    let e1 = world.spawn(I(1));
+    let e2 = world.spawn((U(2), I(2)));
+    let e3 = world.spawn_empty();
+
+    let us = world.comp::<U>();
+    let mixed = [e0, e1, e2, e3];
+    assert_eq!(us.filter_present(&mixed).collect::<Vec<_>>(), [e0, e2]);
+
+    assert!(us.contains_all(&[e0, e2]));
+    assert!(!us.contains_all(&mixed));
+    assert!(us.contains_all(&[]));
+}
+
+#[test]
+fn component_pool_debug() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let e0 = world.spawn(U(10));
+    let e1 = world.spawn(U(20));
+
+    let us = world.comp::<U>();
+    assert_eq!(format!("{:?}", *us), "[U(10), U(20)]");
+
+    let s = format!("{:#?}", *us);
+    assert!(s.contains(&format!("{}: U(", e0)), "{}", s);
+    assert!(s.contains(&format!("{}: U(", e1)), "{}", s);
+}
+
+#[test]
+fn iter_mut_with_entities() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let ents = [10, 20, 30].map(|x| world.spawn(U(x)));
+
+    let mut us = world.comp_mut::<U>();
+    for (ent, u) in us.iter_mut_with_entities() {
+        u.0 += ent.index() as usize;
+    }
+
+    assert_eq!(us.get(ents[0]), Some(&U(10)));
+    assert_eq!(us.get(ents[1]), Some(&U(21)));
+    assert_eq!(us.get(ents[2]), Some(&U(32)));
+}
+
+#[test]
+fn component_pool_from_iter() {
+    let mut world = World::default();
+    let ents = [(); 3].map(|_| world.spawn_empty());
+
+    let mut pool = ents
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (*e, U(i)))
+        .collect::<ComponentPool<_>>();
+    assert_eq!(pool.len(), 3);
+    for (i, e) in ents.iter().enumerate() {
+        assert_eq!(pool.get(*e), Some(&U(i)));
+    }
+
+    // overwrite
+    pool.extend([(ents[1], U(10))]);
+    assert_eq!(pool.len(), 3);
+    assert_eq!(pool.get(ents[1]), Some(&U(10)));
+}
+
+#[test]
+fn memory_usage() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+    let empty = world.comp::<U>().memory_usage();
+
+    let ents = (0..100).map(|i| world.spawn(U(i))).collect::<Vec<_>>();
+    let full = world.comp::<U>().memory_usage();
+    assert!(full > empty);
+
+    let mut us = world.comp_mut::<U>();
+    for ent in &ents[10..] {
+        us.swap_remove(*ent);
+    }
+    assert_eq!(us.memory_usage(), full);
+    us.shrink_to_fit();
+    assert!(us.memory_usage() < full);
+
+    // the sparse array is truncated to the last live entity, too
+    let per_entity = std::mem::size_of::<Option<DenseIndex>>()
+        + std::mem::size_of::<SparseIndex>()
+        + std::mem::size_of::<U>();
+    assert_eq!(us.memory_usage(), 10 * per_entity);
+    drop(us);
+
+    let report = world.memory_report();
+    assert_eq!(report.len(), 2);
+    assert_eq!(report[0].0, std::any::type_name::<U>());
+    assert_eq!(report[1], (std::any::type_name::<I>(), 0));
+}
+
+#[test]
+fn get_many() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let e0 = world.spawn(U(0));
+    let e1 = world.spawn_empty();
+    let e2 = world.spawn(U(2));
+
+    let us = world.comp::<U>();
+    assert_eq!(us.get_many([e0, e1, e2]), [Some(&U(0)), None, Some(&U(2))]);
+}
+
+#[test]
+fn freeze_component_pool() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let ents = (0..4).map(|i| world.spawn(U(i))).collect::<Vec<_>>();
+    world.comp_mut::<U>().freeze();
+
+    // despawning leaves tombstones without reordering
+    world.despawn(ents[0]);
+    world.despawn(ents[2]);
+    {
+        let us = world.comp::<U>();
+        assert_eq!(us.as_slice(), &[U(0), U(1), U(2), U(3)]);
+        assert_eq!(us.entities(), &ents[..]);
+        assert!(us.is_tombstone(0) && us.is_tombstone(2));
+        assert!(!us.contains(ents[0]));
+        assert!(us.get(ents[2]).is_none());
+
+        // tombstones are invisible except in the dense slices
+        assert_eq!(us.len(), 2);
+        let live = us.iter_dense().collect::<Vec<_>>();
+        assert_eq!(live, [(ents[1], &U(1)), (ents[3], &U(3))]);
+    }
+
+    // insertion appends
+    let e4 = world.spawn(U(4));
+    assert_eq!(world.comp::<U>().entities()[4], e4);
+
+    world.comp_mut::<U>().thaw();
+    let us = world.comp::<U>();
+    assert!(!us.is_frozen());
+    assert_eq!(us.as_slice(), &[U(1), U(3), U(4)]);
+    assert_eq!(us.get(ents[3]), Some(&U(3)));
+    assert_eq!(us.get(e4), Some(&U(4)));
+}
+
+#[test]
+fn query_frozen_component_pool() {
+    // three entities, where the second one is despawned while the pool is frozen
+    let frozen_world = || {
+        let mut world = World::default();
+        world.register_set::<(U, I)>();
+
+        let ents = (0..3)
+            .map(|i| world.spawn((U(i), I(-(i as isize)))))
+            .collect::<Vec<_>>();
+        world.comp_mut::<U>().freeze();
+        world.despawn(ents[1]);
+        (world, ents)
+    };
+
+    let (world, ents) = frozen_world();
+    assert_eq!(world.count::<U>(), 2);
+    assert_eq!(
+        world.entities_with::<U>().collect::<Vec<_>>(),
+        [ents[0], ents[2]]
+    );
+
+    {
+        let mut us = world.comp_mut::<U>();
+        assert_eq!((&us).iter().collect::<Vec<_>>(), [&U(0), &U(2)]);
+        let alive = (&mut us).iter().entities().map(|(e, _)| e);
+        assert_eq!(alive.collect::<Vec<_>>(), [ents[0], ents[2]]);
+        assert_eq!(us.iter_mut_with_entities().count(), 2);
+
+        let is = world.comp::<I>();
+        assert_eq!((&us, &is).iter().count(), 2);
+    }
+
+    // tombstones are not merged
+    let mut merged = World::default();
+    let map = merged.merge(world, res::MergePolicy::Keep);
+    assert_eq!(merged.comp::<U>().as_slice(), [U(0), U(2)]);
+    assert_eq!(
+        merged.comp::<U>().entities(),
+        [map[&ents[0]], map[&ents[2]]]
+    );
+
+    let (mut world, _ents) = frozen_world();
+    assert_eq!(world.despawn_all_with::<U>(), 2);
+    assert!(world.comp::<U>().is_empty());
+}
+
+#[test]
+#[should_panic]
+fn freeze_component_pool_take() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let e = world.spawn(U(0));
+    world.comp_mut::<U>().freeze();
+    world.remove::<U>(e);
+}
+
+#[test]
+fn get2_mut_checked() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let e0 = world.spawn(U(0));
+    let e1 = world.spawn_empty();
+    let e2 = world.spawn(U(2));
+
+    let mut us = world.comp_mut::<U>();
+    assert!(us.get2_mut_checked(e0, e0).is_none());
+    assert!(us.get2_mut_checked(e0, e1).is_none());
+
+    let (u0, u2) = us.get2_mut_checked(e0, e2).unwrap();
+    std::mem::swap(u0, u2);
+    assert_eq!(us.get(e0), Some(&U(2)));
+    assert_eq!(us.get(e2), Some(&U(0)));
+}
+
+#[test]
+fn register_with_capacity() {
+    let mut world = World::default();
+
+    assert!(!world.register_with_capacity::<U>(100));
+    assert!(world.register_with_capacity::<U>(1000));
+    assert!(world.comp::<U>().capacity() >= 100);
+    assert_eq!(world.comp::<U>().len(), 0);
 }
 
 #[test]
@@ -196,6 +769,51 @@ fn ignore_dead_entity() {
     println!("{:#?}", world.display());
 }
 
+#[test]
+fn insert_set_dead_entity() {
+    /// Inserts to the pool directly, without going through `World::insert`
+    struct RawU(U);
+
+    impl ComponentSet for RawU {
+        fn register(map: &mut ComponentPoolMap) {
+            map.register::<U>();
+        }
+
+        fn insert(self, ent: Entity, world: &mut World) {
+            world.comp.try_borrow_mut::<U>().unwrap().insert(ent, self.0);
+        }
+
+        fn remove(ent: Entity, world: &mut World) {
+            world.remove::<U>(ent);
+        }
+
+        fn take(ent: Entity, world: &mut World) -> Option<Self> {
+            world.remove::<U>(ent).map(RawU)
+        }
+
+        fn type_ids() -> Box<[TypeId]> {
+            Box::new([TypeId::of::<U>()])
+        }
+    }
+
+    let mut world = World::default();
+    world.register_set::<RawU>();
+
+    let dead = world.spawn_empty();
+    world.despawn(dead);
+
+    // the liveness is checked before the set is inserted
+    world.insert_set(dead, RawU(U(10)));
+    assert!(world.comp.try_borrow::<U>().unwrap().as_slice().is_empty());
+
+    let alive = world.spawn_empty();
+    world.insert_set(alive, RawU(U(20)));
+    assert_eq!(
+        world.comp.try_borrow::<U>().unwrap().get(alive),
+        Some(&U(20))
+    );
+}
+
 #[test]
 fn pointer_stability_after_display() {
     let mut world = World::default();
@@ -281,6 +899,120 @@ fn confliction() {
     }
 }
 
+#[test]
+fn frozen_confliction() {
+    use crate::world::fetch::{Access, AccessSet};
+
+    struct Ty<const N: usize>;
+
+    macro_rules! type_ids {
+        ($($n:literal)*) => {
+            [$(TypeId::of::<Ty<$n>>(),)*]
+        };
+    }
+
+    let tys = type_ids!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23);
+
+    // linear congruential generator for deterministic access sets
+    let mut seed = 12345u32;
+    let mut rand = move || {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as usize
+    };
+
+    let sets = (0..32)
+        .map(|_| {
+            let accesses = (0..8)
+                .map(|_| {
+                    let ty = tys[rand() % tys.len()];
+                    match rand() % 16 {
+                        0..=3 => Access::Res(ty),
+                        4..=7 => Access::ResMut(ty),
+                        8..=11 => Access::Comp(ty),
+                        12..=14 => Access::CompMut(ty),
+                        _ if rand() % 2 == 0 => Access::Entities,
+                        _ => Access::EntitiesMut,
+                    }
+                })
+                .collect::<Vec<_>>();
+            AccessSet::new(accesses)
+        })
+        .collect::<Vec<_>>();
+
+    let mut n_conflicts = 0;
+    for a in &sets {
+        for b in &sets {
+            let naive = a.conflicts(b);
+            assert_eq!(naive, a.freeze().conflicts(&b.freeze()));
+            n_conflicts += naive as usize;
+        }
+    }
+
+    // make sure both branches are tested
+    assert!(0 < n_conflicts && n_conflicts < sets.len() * sets.len());
+}
+
+#[test]
+fn entity_access() {
+    use crate::world::fetch::{Access, AccessSet};
+
+    fn sys(_: &EntityPool, _: Comp<U>) {}
+    let accesses = sys.accesses();
+    assert!(accesses.as_slice().contains(&Access::Entities));
+
+    let writes = AccessSet::new(vec![Access::EntitiesMut]);
+    assert!(!accesses.self_conflict());
+    assert!(accesses.conflicts(&writes));
+    assert!(accesses.freeze().conflicts(&writes.freeze()));
+}
+
+#[test]
+fn conflicting_types() {
+    use crate::world::fetch::Access;
+
+    fn reads(_: Res<U>, _: Comp<U>, _: Res<I>) {}
+    fn writes(_: ResMut<U>, _: Comp<U>) {}
+
+    let (r, w) = (reads.accesses(), writes.accesses());
+    let ty = TypeId::of::<U>();
+    assert_eq!(r.conflicting_types(&w), [Access::Res(ty)]);
+    assert_eq!(w.conflicting_types(&r), [Access::ResMut(ty)]);
+    assert!(r.conflicting_types(&r).is_empty());
+
+    fn self_conflict(_: Comp<I>, _: Res<U>, _: CompMut<I>) {}
+    let ty = TypeId::of::<I>();
+    assert!(r.self_conflicting_types().is_empty());
+    assert_eq!(
+        self_conflict.accesses().self_conflicting_types(),
+        [Access::Comp(ty), Access::CompMut(ty)]
+    );
+}
+
+#[test]
+fn register_erased() {
+    let mut world = World::default();
+    let ty = TypeId::of::<U>();
+
+    assert!(!world
+        .comp
+        .register_erased(ty, || Box::new(ComponentPool::<U>::default())));
+    assert!(world.comp.register_erased(ty, || unreachable!()));
+    assert!(world.comp.register::<U>());
+
+    let e = world.spawn(U(10));
+    assert_eq!(world.comp::<U>().get(e), Some(&U(10)));
+}
+
+#[test]
+#[should_panic]
+fn register_erased_mismatch() {
+    let mut comp = ComponentPoolMap::default();
+    comp.register_erased(
+        TypeId::of::<I>(),
+        || Box::new(ComponentPool::<U>::default()),
+    );
+}
+
 #[test]
 fn entity_reservation() {
     let mut ents = EntityPool::default();
@@ -308,6 +1040,35 @@ fn entity_reservation() {
     assert!(ents.contains(e0) && ents.contains(e1), "sparse array error");
 }
 
+#[test]
+fn entity_contains_reserved() {
+    let mut ents = EntityPool::default();
+    let [e0, e1, e2] = [(); 3].map(|_| ents.alloc());
+    ents.dealloc(e0);
+    ents.dealloc(e1);
+
+    // two recycled slots and one new slot
+    let ra = ents.reserve_atomic();
+    let rb = ents.reserve_atomic();
+    let rc = ents.reserve_atomic();
+    assert_eq!((ra.index(), rb.index(), rc.index()), (1, 0, 3));
+
+    for r in [ra, rb, rc] {
+        assert!(!ents.contains(r) && ents.contains_reserved(r));
+    }
+    assert!(ents.contains_reserved(e2));
+
+    // dead or not reserved
+    assert!(!ents.contains_reserved(e0) && !ents.contains_reserved(e1));
+    let rd = Entity(SparseIndex::initial(RawSparseIndex::from_usize(4)));
+    assert!(!ents.contains_reserved(rd));
+
+    ents.synchronize();
+    for r in [ra, rb, rc] {
+        assert!(ents.contains(r) && ents.contains_reserved(r));
+    }
+}
+
 #[test]
 fn commands() {
     let mut world = World::default();
@@ -349,3 +1110,52 @@ fn commands() {
     assert_eq!(u.as_slice().len(), 2);
     assert_eq!(i.as_slice().len(), 2);
 }
+
+#[test]
+fn commands_clear() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::cmd::{Command, CommandQueue};
+
+    struct DropCount(Arc<AtomicUsize>);
+
+    impl Drop for DropCount {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Command for DropCount {
+        fn write(self, _world: &mut World) {
+            unreachable!();
+        }
+    }
+
+    let n_drops = Arc::new(AtomicUsize::new(0));
+
+    let mut cmds = CommandQueue::default();
+    cmds.push(DropCount(n_drops.clone()));
+    cmds.push(|_: &mut World| {});
+    cmds.push(DropCount(n_drops.clone()));
+    assert_eq!(cmds.len(), 3);
+
+    cmds.clear();
+    assert!(cmds.is_empty());
+    assert_eq!(n_drops.load(Ordering::SeqCst), 2);
+
+    // the queue is still usable
+    let mut world = World::default();
+    cmds.push(|world: &mut World| {
+        world.set_res(U(10));
+    });
+    cmds.apply(&mut world);
+    assert_eq!(*world.res::<U>(), U(10));
+
+    // un-applied commands are dropped along with the queue
+    cmds.push(DropCount(n_drops.clone()));
+    drop(cmds);
+    assert_eq!(n_drops.load(Ordering::SeqCst), 3);
+}
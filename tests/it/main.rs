@@ -4,8 +4,11 @@ use toecs::{
     query::Iter,
     sys::System,
     world::{
-        comp::{Comp, CompMut, Component},
-        res::{Res, ResMut},
+        comp::{self, Comp, CompMut, Component},
+        ent::{DespawnError, Entity, MapEntities},
+        fetch::WorldRef,
+        hierarchy::{Children, Parent},
+        res::{self, Res, ResMut},
     },
     World,
 };
@@ -58,6 +61,143 @@ fn world_api() {
     println!("{:#?}", world.display());
 }
 
+#[test]
+fn run_owned_return() {
+    let mut world = World::default();
+    world.register::<U>();
+    let e0 = world.spawn(U(0));
+    world.spawn_empty();
+    let e2 = world.spawn(U(2));
+
+    fn entities(u: Comp<U>) -> Vec<Entity> {
+        u.entities().to_vec()
+    }
+
+    fn values(u: Comp<U>) -> Vec<U> {
+        u.iter().copied().collect()
+    }
+
+    assert_eq!(world.run(entities), [e0, e2]);
+    assert_eq!(world.run(values), [U(0), U(2)]);
+
+    // the borrow is released once the system returns
+    world.despawn(e0);
+    assert_eq!(world.run(entities), [e2]);
+}
+
+#[test]
+fn despawn_all_with() {
+    #[derive(Component, Debug)]
+    struct Projectile;
+
+    let mut world = World::default();
+    assert_eq!(world.despawn_all_with::<Projectile>(), 0);
+
+    world.register_set::<(U, Projectile)>();
+    let p0 = world.spawn((U(0), Projectile));
+    let e1 = world.spawn(U(1));
+    let p2 = world.spawn(Projectile);
+    let e3 = world.spawn_empty();
+
+    assert_eq!(world.despawn_all_with::<Projectile>(), 2);
+    assert!(!world.contains(p0) && !world.contains(p2));
+    assert!(world.contains(e1) && world.contains(e3));
+    assert!(world.comp::<Projectile>().is_empty());
+    assert_eq!(world.comp::<U>().as_slice(), [U(1)]);
+}
+
+#[test]
+fn entities_with() {
+    #[derive(Component, Debug)]
+    struct Tag;
+
+    let mut world = World::default();
+    assert_eq!(world.entities_with::<Tag>().count(), 0);
+
+    world.register::<Tag>();
+    let e0 = world.spawn(Tag);
+    world.spawn_empty();
+    let e2 = world.spawn(Tag);
+
+    assert_eq!(world.entities_with::<Tag>().collect::<Vec<_>>(), [e0, e2]);
+}
+
+#[test]
+fn count() {
+    let mut world = World::default();
+    assert_eq!(world.count::<U>(), 0);
+
+    world.register::<U>();
+    assert_eq!(world.count::<U>(), 0);
+
+    let e0 = world.spawn(U(0));
+    world.spawn(U(1));
+    world.spawn_empty();
+    assert_eq!(world.count::<U>(), 2);
+
+    world.remove::<U>(e0);
+    assert_eq!(world.count::<U>(), 1);
+}
+
+#[test]
+fn try_despawn() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let e = world.spawn(U(0));
+    assert_eq!(world.try_despawn(e), Ok(()));
+    assert!(world.comp::<U>().as_slice().is_empty());
+    assert_eq!(world.try_despawn(e), Err(DespawnError::Dead(e)));
+
+    // stale generation
+    let e_new = world.spawn_empty();
+    assert_ne!(e, e_new);
+    assert_eq!(world.try_despawn(e), Err(DespawnError::Dead(e)));
+    assert!(world.contains(e_new));
+
+    // out of range
+    let reserved = world.reserve_atomic();
+    assert_eq!(
+        world.try_despawn(reserved),
+        Err(DespawnError::NeverSpawned(reserved))
+    );
+}
+
+#[test]
+fn spawn_each() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    let ents = world.spawn_each(vec![U(0), U(1), U(2)]);
+    assert_eq!(ents.len(), 3);
+    let us = world.comp::<U>();
+    for (i, ent) in ents.iter().enumerate() {
+        assert_eq!(us.get(*ent), Some(&U(i)));
+    }
+    drop(us);
+
+    let ents = world.spawn_each((0..2).map(|i| (U(i), I(-(i as isize)))));
+    assert_eq!(world.comp::<I>().get(ents[1]), Some(&I(-1)));
+}
+
+#[test]
+fn spawn_reserved() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    let e0 = world.spawn(U(0));
+    world.despawn(e0);
+
+    let e1 = world.reserve_atomic();
+    let e2 = world.reserve_atomic();
+    world.spawn_reserved(e2, (U(20), I(20)));
+
+    assert!(world.contains(e1) && world.contains(e2));
+    assert_eq!(world.comp::<U>().get(e2), Some(&U(20)));
+    assert_eq!(world.comp::<I>().get(e2), Some(&I(20)));
+    assert_eq!(world.comp::<U>().get(e1), None);
+}
+
 #[test]
 fn single_iter() {
     let mut world = World::default();
@@ -190,6 +330,45 @@ fn sparse_iter_holes() {
     );
 }
 
+#[test]
+fn single_query() {
+    let mut world = World::default();
+    world.register_set::<(U, I, F)>();
+
+    let e = world.spawn((U(10), I(-10)));
+    world.spawn(U(20));
+    world.spawn(F(30.0));
+    world.spawn(F(40.0));
+
+    let mut u = world.comp_mut::<U>();
+    let i = world.comp::<I>();
+    let f = world.comp::<F>();
+
+    // one
+    assert_eq!((&i).try_single(), Some(&I(-10)));
+    assert_eq!((&u, &i).single(), (&U(10), &I(-10)));
+    (&mut u, &i).single().0 .0 += 1;
+    assert_eq!(u.get(e), Some(&U(11)));
+
+    // zero
+    assert_eq!((&u, &f).try_single(), None);
+
+    // many
+    assert_eq!((&f).try_single(), None);
+    assert_eq!((&u).try_single(), None);
+}
+
+#[test]
+#[should_panic]
+fn single_query_panic() {
+    let mut world = World::default();
+    world.register::<U>();
+    world.spawn(U(10));
+    world.spawn(U(20));
+
+    let _ = (&world.comp::<U>()).single();
+}
+
 #[test]
 fn borrow_type_inference() {
     let mut world = World::default();
@@ -210,6 +389,55 @@ fn borrow_type_inference() {
     let (_, _, _, _): (Res<U>, Res<I>, Comp<U>, CompMut<I>) = world.fetch();
 }
 
+#[test]
+#[should_panic(expected = "Comp<it::U>, CompMut<it::U>")]
+fn run_exclusive_self_conflict() {
+    let mut world = World::default();
+    world.register::<U>();
+    world.set_res(I(0));
+
+    fn sys(_u: Comp<U>, _i: Res<I>, _u_mut: CompMut<U>) {}
+    world.run_ex(sys);
+}
+
+#[test]
+fn report_accesses() {
+    let mut world = World::default();
+    world.register::<U>();
+    world.set_res(I(0));
+
+    fn sys(_u: Comp<U>, _i: ResMut<I>, _ents: &toecs::world::ent::EntityPool) {}
+
+    let accesses = World::system_accesses(&sys);
+    let report = world.report_accesses(&accesses);
+    println!("{:?}", report);
+    assert_eq!(report, ["Comp<it::U>", "ResMut<it::I>", "&EntityPool"]);
+}
+
+#[test]
+fn run_ex_result() {
+    use toecs::sys::erased::SystemResult;
+
+    fn ok_sys(world: &mut World) {
+        world.set_res(U(0));
+    }
+
+    fn err_sys(world: &mut World) -> SystemResult {
+        world.res_mut::<U>().0 += 1;
+        Err(anyhow::anyhow!("error"))
+    }
+
+    fn run(world: &mut World) -> SystemResult {
+        world.run_ex_result(ok_sys)?;
+        world.run_ex_result(err_sys)?;
+        unreachable!();
+    }
+
+    let mut world = World::default();
+    assert_eq!(run(&mut world).unwrap_err().to_string(), "error");
+    assert_eq!(*world.res::<U>(), U(1));
+}
+
 #[test]
 fn run_exclusive() {
     let mut world = World::default();
@@ -243,6 +471,84 @@ fn run_with_args() {
     assert_eq!(world.run_arg(sys, 10u32), 10);
 }
 
+#[test]
+fn run_no_params() {
+    let world = World::default();
+
+    fn constant() -> usize {
+        10
+    }
+    assert_eq!(world.run(constant), 10);
+
+    let mut n = 0;
+    world.run(|| n += 1);
+    world.run(|| n += 1);
+    assert_eq!(n, 2);
+}
+
+#[test]
+#[cfg(feature = "rayon")]
+fn run_par_chunks() {
+    let mut world = World::default();
+    world.register::<U>();
+    for i in 0..100_000 {
+        world.spawn(U(i));
+    }
+
+    world.run_par_chunks::<U, _>(1024, |us| us.iter_mut().for_each(|u| u.0 *= 2));
+
+    let expected = (0..100_000).map(|i| U(i * 2)).collect::<Vec<_>>();
+    assert_eq!(world.comp::<U>().as_slice(), &expected[..]);
+}
+
+#[test]
+fn run_arg_res() {
+    let mut world = World::default();
+    world.set_res_set((U(1), I(0)));
+
+    fn sys(u: &mut U, mut i: ResMut<I>) -> usize {
+        u.0 += 1;
+        i.0 = u.0 as isize;
+        u.0
+    }
+
+    assert_eq!(world.run_arg_res(sys), 2);
+    assert_eq!(*world.res::<U>(), U(2));
+    assert_eq!(*world.res::<I>(), I(2));
+
+    // the resource is restored even if the system panics
+    fn panic_sys(_u: &mut U, _i: Res<I>) {
+        panic!();
+    }
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        world.run_arg_res(panic_sys);
+    }));
+    assert!(result.is_err());
+    assert_eq!(*world.res::<U>(), U(2));
+}
+
+#[test]
+fn world_ref() {
+    let mut world = World::default();
+    world.register::<Link>();
+
+    let target = world.spawn_empty();
+    let dead = world.spawn_empty();
+    world.despawn(dead);
+    world.spawn(Link(target));
+    world.spawn(Link(dead));
+
+    fn count_alive_links(world: WorldRef, links: Comp<Link>) -> usize {
+        links.iter().filter(|l| world.contains(l.0)).count()
+    }
+
+    assert_eq!(world.run(count_alive_links), 1);
+
+    fn conflict(_world: WorldRef, _links: CompMut<Link>) {}
+    assert!(conflict.accesses().self_conflict());
+}
+
 #[test]
 fn component_set_definition() {
     let mut world = World::default();
@@ -257,7 +563,1237 @@ fn component_set_definition() {
     world.insert_set(entity, (I(1), U(1)));
 
     // set of component set is a component set
-    world.insert_set(entity, ((U(2), I(2), F(2.2)), (U(3), I(3))));
+    world.insert_set(entity, ((U(2), I(2)), F(2.2)));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic]
+fn component_set_duplicate() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    let entity = world.spawn_empty();
+    world.insert_set(entity, ((U(0), I(0)), U(1)));
+}
+
+/// Matches both `AlreadyBorrowed` and `AlreadyBorrowedAt`
+fn is_already_borrowed<T, E: std::fmt::Display>(res: Result<T, E>) -> bool {
+    res.err()
+        .is_some_and(|err| err.to_string().contains("already borrowed"))
+}
+
+#[test]
+fn try_borrow() {
+    let mut world = World::default();
+
+    assert!(matches!(
+        world.try_res::<U>(),
+        Err(res::BorrowError::NotFound(_))
+    ));
+    assert!(matches!(
+        world.try_comp::<U>(),
+        Err(comp::BorrowError::NotRegistered(_))
+    ));
+
+    world.set_res(U(0));
+    world.register::<U>();
+
+    {
+        let _r = world.try_res_mut::<U>().unwrap();
+        assert!(is_already_borrowed(world.try_res::<U>()));
+        assert!(is_already_borrowed(world.try_res_mut::<U>()));
+
+        #[cfg(not(feature = "debug-borrow-tracking"))]
+        assert!(matches!(
+            world.try_res::<U>(),
+            Err(res::BorrowError::AlreadyBorrowed(_))
+        ));
+    }
+
+    {
+        let _c = world.try_comp::<U>().unwrap();
+        assert!(world.try_comp::<U>().is_ok());
+        assert!(is_already_borrowed(world.try_comp_mut::<U>()));
+    }
+
+    assert!(world.try_res_mut::<U>().is_ok());
+    assert!(world.try_comp_mut::<U>().is_ok());
+}
+
+#[test]
+#[cfg(feature = "debug-borrow-tracking")]
+fn borrow_tracking() {
+    let mut world = World::default();
+    world.set_res(U(0));
+    world.register::<U>();
+
+    let _r = world.res_mut::<U>();
+    let line = line!() - 1;
+    let err = world.try_res::<U>().unwrap_err();
+    assert_eq!(err.location().map(|loc| loc.line()), Some(line));
+    let err = err.to_string();
+    assert!(err.contains(&format!("{}:{}", file!(), line)), "{}", err);
+
+    let _c = world.comp_mut::<U>();
+    let line = line!() - 1;
+    let err = world.try_comp::<U>().unwrap_err();
+    assert!(matches!(err, comp::BorrowError::AlreadyBorrowedAt(..)));
+    let err = err.to_string();
+    assert!(err.contains(&format!("{}:{}", file!(), line)), "{}", err);
+}
+
+#[test]
+fn despawn_recursive() {
+    let mut world = World::default();
+    world.register_set::<(U, Parent, Children)>();
+
+    // root - a - (a1, a2)
+    //      - b
+    let root = world.spawn(U(0));
+    let a = world.spawn((U(1), Parent(root)));
+    let b = world.spawn((U(2), Parent(root)));
+    let a1 = world.spawn((U(3), Parent(a)));
+    let a2 = world.spawn((U(4), Parent(a)));
+    world.insert(root, Children(vec![a, b]));
+    world.insert(a, Children(vec![a1, a2]));
+
+    // make a cycle
+    world.insert(a2, Children(vec![root]));
+
+    let other = world.spawn(U(5));
+
+    assert!(world.despawn_recursive(root));
+    assert!(!world.despawn_recursive(root));
+
+    for e in [root, a, b, a1, a2] {
+        assert!(!world.contains(e));
+    }
+    assert_eq!(world.entities(), &[other]);
+    assert_eq!(world.comp::<U>().as_slice(), &[U(5)]);
+    assert!(world.comp::<Parent>().as_slice().is_empty());
+    assert!(world.comp::<Children>().as_slice().is_empty());
+
+    // the despawned entity is removed from the parent's children
+    let parent = world.spawn_empty();
+    let child = world.spawn(Parent(parent));
+    world.insert(parent, Children(vec![child]));
+    world.despawn_recursive(child);
+    assert_eq!(world.comp::<Children>().get(parent), Some(&Children(vec![])));
+}
+
+#[test]
+fn hierarchy_consistency() {
+    let mut world = World::default();
+
+    let p1 = world.spawn_empty();
+    let p2 = world.spawn_empty();
+    let c = world.spawn_empty();
+
+    assert!(world.add_child(p1, c));
+    assert_eq!(world.comp::<Parent>().get(c), Some(&Parent(p1)));
+    assert_eq!(world.comp::<Children>().get(p1), Some(&Children(vec![c])));
+
+    // adding twice doesn't duplicate the child
+    assert!(world.add_child(p1, c));
+    assert_eq!(world.comp::<Children>().get(p1), Some(&Children(vec![c])));
+
+    // reparent
+    assert!(world.add_child(p2, c));
+    assert_eq!(world.comp::<Parent>().get(c), Some(&Parent(p2)));
+    assert_eq!(world.comp::<Children>().get(p1), Some(&Children(vec![])));
+    assert_eq!(world.comp::<Children>().get(p2), Some(&Children(vec![c])));
+
+    // remove
+    assert!(!world.remove_child(p1, c));
+    assert!(world.remove_child(p2, c));
+    assert_eq!(world.comp::<Parent>().get(c), None);
+    assert_eq!(world.comp::<Children>().get(p2), Some(&Children(vec![])));
+
+    // dead entity
+    world.despawn(p1);
+    assert!(!world.add_child(p1, c));
+}
+
+#[test]
+fn hierarchy_iteration() {
+    let mut world = World::default();
+
+    // root - a - (a1, a2)
+    //      - b
+    let root = world.spawn_empty();
+    let [a, b, a1, a2] = [(); 4].map(|_| world.spawn_empty());
+    world.add_child(root, a);
+    world.add_child(root, b);
+    world.add_child(a, a1);
+    world.add_child(a, a2);
+
+    assert_eq!(
+        world.descendants(root).collect::<Vec<_>>(),
+        [a, a1, a2, b]
+    );
+    assert_eq!(world.descendants(b).count(), 0);
+    assert_eq!(world.ancestors(a2).collect::<Vec<_>>(), [a, root]);
+    assert_eq!(world.ancestors(root).count(), 0);
+
+    // make a cycle
+    world.add_child(a2, root);
+    assert_eq!(
+        world.descendants(root).collect::<Vec<_>>(),
+        [a, a1, a2, b]
+    );
+    assert_eq!(world.ancestors(a2).collect::<Vec<_>>(), [a, root]);
+
+    // no hierarchy component is registered
+    let mut world = World::default();
+    let e = world.spawn_empty();
+    assert_eq!(world.descendants(e).count(), 0);
+    assert_eq!(world.ancestors(e).count(), 0);
+}
+
+#[test]
+#[should_panic]
+fn hierarchy_iteration_borrowed() {
+    let mut world = World::default();
+    let [parent, child] = [(); 2].map(|_| world.spawn_empty());
+    world.add_child(parent, child);
+
+    let _children = world.comp_mut::<Children>();
+    world.descendants(parent).count();
+}
+
+#[test]
+fn iter_children_components() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    // no hierarchy yet
+    assert_eq!(world.iter_children_components::<U>().iter().count(), 0);
+
+    // root - a - (a1, a2)
+    //      - b
+    let root = world.spawn(U(0));
+    let [a, b, a1, a2] = [1, 2, 3, 4].map(|i| world.spawn(U(i)));
+    let c = world.spawn_empty();
+    world.add_child(root, a);
+    world.add_child(root, b);
+    world.add_child(a, a1);
+    world.add_child(a, a2);
+    world.add_child(a, c);
+
+    let sums = world
+        .iter_children_components::<U>()
+        .iter()
+        .map(|(parent, us)| (parent, us.map(|(_, u)| u.0).sum::<usize>()))
+        .collect::<Vec<_>>();
+    assert_eq!(sums, [(root, 1 + 2), (a, 3 + 4)]);
+}
+
+#[test]
+fn resources_scope() {
+    let mut world = World::default();
+    world.set_res_set((U(10), I(-10)));
+
+    let mut n_visits = 0;
+    world.resources_scope(|res| {
+        n_visits += 1;
+        if let Some(u) = res.downcast_mut::<U>() {
+            u.0 += 1;
+        } else if let Some(i) = res.downcast_mut::<I>() {
+            i.0 -= 1;
+        } else {
+            unreachable!();
+        }
+    });
+
+    assert_eq!(n_visits, 2);
+    assert_eq!(*world.res::<U>(), U(11));
+    assert_eq!(*world.res::<I>(), I(-11));
+}
+
+#[test]
+fn set_res_with() {
+    let mut world = World::default();
+    world.register::<U>();
+    world.spawn(U(0));
+    world.spawn(U(1));
+
+    world.set_res_with(|world| U(world.comp::<U>().len()));
+    assert_eq!(*world.res::<U>(), U(2));
+
+    // last writer wins
+    world.set_res_with(|world| {
+        world.set_res(U(100));
+        U(200)
+    });
+    assert_eq!(*world.res::<U>(), U(200));
+}
+
+#[test]
+fn replace_res_with() {
+    let mut world = World::default();
+
+    world.replace_res_with(|old: Option<Vec<usize>>| {
+        assert!(old.is_none());
+        vec![0]
+    });
+
+    world.replace_res_with(|old: Option<Vec<usize>>| {
+        let mut log = old.unwrap();
+        log.push(1);
+        log
+    });
+    assert_eq!(*world.res::<Vec<usize>>(), [0, 1]);
+
+    // the old value is consumed on panic
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        world.replace_res_with(|_: Option<Vec<usize>>| panic!());
+    }));
+    assert!(result.is_err());
+    assert!(world.try_res::<Vec<usize>>().is_err());
+}
+
+#[test]
+fn set_res_if_new() {
+    let mut world = World::default();
+
+    assert!(world.set_res_if_new(U(0)));
+    assert!(!world.set_res_if_new(U(1)));
+    assert_eq!(*world.res::<U>(), U(0));
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Config(usize);
+
+    *world.init_res::<Config>() = Config(10);
+    assert_eq!(*world.init_res::<Config>(), Config(10));
+}
+
+#[test]
+fn init_res_with_system() {
+    #[derive(Debug, PartialEq)]
+    struct Count(usize);
+
+    let mut world = World::default();
+    world.register::<U>();
+    world.spawn(U(0));
+    world.spawn(U(1));
+
+    fn count(us: Comp<U>) -> Count {
+        Count(us.len())
+    }
+
+    world.init_res_with_system(count).0 += 10;
+    assert_eq!(*world.res::<Count>(), Count(2 + 10));
+
+    // the system doesn't run if the resource is present
+    world.spawn(U(2));
+    assert_eq!(*world.init_res_with_system(count), Count(2 + 10));
+}
+
+#[test]
+fn try_run() {
+    use std::any::TypeId;
+    use toecs::sys::RunError;
+
+    fn sys(_: Res<U>, _: Comp<I>) -> usize {
+        1
+    }
+
+    let mut world = World::default();
+    assert_eq!(
+        world.try_run(sys),
+        Err(RunError::ResourceNotFound(TypeId::of::<U>()))
+    );
+
+    world.set_res(U(0));
+    assert_eq!(
+        world.try_run(sys),
+        Err(RunError::ComponentNotRegistered(TypeId::of::<I>()))
+    );
+
+    world.register::<I>();
+    assert_eq!(world.try_run(sys), Ok(1));
+}
+
+#[test]
+fn world_get() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let e0 = world.spawn(U(0));
+    let e1 = world.spawn_empty();
+
+    // present
+    assert_eq!(world.get::<U>(e0).as_deref(), Some(&U(0)));
+    world.get_mut::<U>(e0).unwrap().0 = 10;
+    assert_eq!(world.get::<U>(e0).as_deref(), Some(&U(10)));
+
+    // absent
+    assert!(world.get::<U>(e1).is_none());
+    assert!(world.get_mut::<U>(e1).is_none());
+
+    // unregistered
+    assert!(world.get::<I>(e0).is_none());
+    assert!(world.get_mut::<I>(e0).is_none());
+
+    // dead
+    world.despawn(e0);
+    let e2 = world.spawn(U(2));
+    assert!(world.get::<U>(e0).is_none());
+    assert!(world.get_mut::<U>(e0).is_none());
+    assert_eq!(world.get::<U>(e2).as_deref(), Some(&U(2)));
+}
+
+#[test]
+fn run_if_registered() {
+    fn sys(_: Comp<U>, mut i: CompMut<I>) -> usize {
+        i.as_mut_slice().len()
+    }
+
+    let mut world = World::default();
+    world.register::<I>();
+    assert_eq!(world.run_if_registered(sys), None);
+
+    world.register::<U>();
+    world.spawn(I(0));
+    assert_eq!(world.run_if_registered(sys), Some(1));
+}
+
+#[test]
+fn remove_all_components() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    let e0 = world.spawn((U(0), I(0)));
+    let e1 = world.spawn((U(1), I(1)));
+
+    assert!(world.remove_all_components(e0));
+    assert!(world.contains(e0));
+    assert!(!world.comp::<U>().contains(e0));
+    assert!(!world.comp::<I>().contains(e0));
+
+    // other entities are left untouched
+    assert_eq!(world.comp::<U>().get(e1), Some(&U(1)));
+    assert_eq!(world.comp::<I>().get(e1), Some(&I(1)));
+
+    world.despawn(e0);
+    assert!(!world.remove_all_components(e0));
+}
+
+#[test]
+fn res_passthrough() {
+    let mut world = World::default();
+    world.set_res(10i32);
+
+    assert_eq!(world.res::<i32>(), 10);
+    assert_eq!(format!("{}", world.res::<i32>()), "10");
+
+    let mut x = world.res_mut::<i32>();
+    *x += 1;
+    assert_eq!(x, 11);
+    assert_eq!(x.to_string(), "11");
+}
+
+#[test]
+fn res_map() {
+    #[derive(Debug)]
+    struct Config {
+        speed: U,
+        limit: Option<I>,
+    }
+
+    let mut world = World::default();
+    world.set_res(Config {
+        speed: U(1),
+        limit: None,
+    });
+
+    fn helper(mut speed: ResMut<U>) {
+        speed.0 += 1;
+    }
+
+    helper(ResMut::map(world.res_mut::<Config>(), |c| &mut c.speed));
+
+    let speed = Res::map(world.res::<Config>(), |c| &c.speed);
+    assert_eq!(*speed, U(2));
+    assert!(world.try_res_mut::<Config>().is_err());
+    drop(speed);
+
+    assert!(Res::filter_map(world.res::<Config>(), |c| c.limit.as_ref()).is_none());
+    world.res_mut::<Config>().limit = Some(I(10));
+    let limit = ResMut::filter_map(world.res_mut::<Config>(), |c| c.limit.as_mut());
+    assert_eq!(limit.as_deref(), Some(&I(10)));
+}
+
+#[test]
+fn res_entry() {
+    let mut world = World::default();
+
+    // absent
+    let u = world.res_entry::<U>().and_modify(|u| u.0 += 1).or_insert(U(10));
+    assert_eq!(*u, U(10));
+
+    // present
+    let u = world.res_entry::<U>().and_modify(|u| u.0 += 1).or_insert(U(10));
+    assert_eq!(*u, U(11));
+
+    world.res_entry::<I>().or_insert_with(|| I(-1)).0 -= 1;
+    assert_eq!(*world.res::<I>(), I(-2));
+}
+
+#[test]
+fn spawn_deferred() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    // reserve in a read context
+    let (e0, f0) = world.spawn_deferred(U(0));
+    let (e1, f1) = world.spawn_deferred((U(1), I(1)));
+    assert!(!world.contains(e0) && !world.contains(e1));
+
+    // apply at the sync point
+    f1(&mut world);
+    f0(&mut world);
+
+    assert!(world.contains(e0) && world.contains(e1));
+    assert_eq!(world.comp::<U>().get(e0), Some(&U(0)));
+    assert_eq!(world.comp::<I>().get(e0), None);
+    assert_eq!(world.comp::<U>().get(e1), Some(&U(1)));
+    assert_eq!(world.comp::<I>().get(e1), Some(&I(1)));
+}
+
+#[test]
+fn for_each_entity() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    world.spawn(U(1));
+    world.spawn((U(2), I(2)));
+    world.spawn(I(3));
+    world.spawn_empty();
+
+    let mut n = 0;
+    let mut sum = 0;
+    world.for_each_entity(|e| {
+        if e.contains::<U>() {
+            n += 1;
+            sum += e.with(|u: &U| u.0).unwrap();
+        }
+        assert!(!e.contains::<F>());
+    });
+
+    assert_eq!(n, 2);
+    assert_eq!(sum, 3);
+
+    let e = world.spawn(U(4));
+    assert_eq!(world.entity(e).unwrap().id(), e);
+    world.despawn(e);
+    assert!(world.entity(e).is_none());
+}
+
+#[test]
+fn trait_object_resource() {
+    trait Counter: std::fmt::Debug {
+        fn count(&mut self) -> usize;
+    }
+
+    #[derive(Debug)]
+    struct Step(usize, usize);
+
+    impl Counter for Step {
+        fn count(&mut self) -> usize {
+            self.0 += self.1;
+            self.0
+        }
+    }
+
+    let mut world = World::default();
+    world.set_res::<Box<dyn Counter>>(Box::new(Step(0, 2)));
+
+    fn sys(mut counter: ResMut<Box<dyn Counter>>) -> usize {
+        counter.count()
+    }
+
+    assert_eq!(world.run(sys), 2);
+    assert_eq!(world.res_mut::<Box<dyn Counter>>().count(), 4);
+    assert!(world.take_res::<Box<dyn Counter>>().is_some());
+}
+
+#[test]
+fn res_split_fields() {
+    #[derive(Debug)]
+    struct Pair {
+        u: U,
+        i: I,
+    }
+
+    fn inc(u: &mut U) {
+        u.0 += 1;
+    }
+
+    fn dec(i: &mut I) {
+        i.0 -= 1;
+    }
+
+    let mut world = World::default();
+    world.set_res(Pair { u: U(0), i: I(0) });
+
+    let mut pair = world.res_mut::<Pair>();
+    let Pair { u, i } = &mut *pair;
+    inc(u);
+    dec(i);
+    drop(pair);
+
+    let pair = world.res::<Pair>();
+    assert_eq!((pair.u, pair.i), (U(1), I(-1)));
+}
+
+#[test]
+fn get_cloned() {
+    let mut world = World::default();
+    world.register::<U>();
+    let e = world.spawn(U(1));
+
+    // the borrow is released soon
+    let u = world.comp::<U>().get_cloned(e).unwrap();
+    world.comp_mut::<U>()[e].0 += u.0;
+    assert_eq!(world.comp::<U>().get(e), Some(&U(2)));
+
+    assert_eq!(world.res_cloned::<I>(), None);
+    world.set_res(I(-1));
+    let i = world.res_cloned::<I>().unwrap();
+    world.res_mut::<I>().0 += i.0;
+    assert_eq!(*world.res::<I>(), I(-2));
+}
+
+#[test]
+fn world_commands() {
+    use toecs::cmd::{self, CommandQueue};
+
+    let mut world = World::default();
+    world.register::<U>();
+
+    // applying no command is a no-op
+    world.apply_commands();
+
+    world.commands().push(cmd::Spawn { comp: U(0) });
+
+    fn sys(mut cmds: ResMut<CommandQueue>) {
+        cmds.push(|world: &mut World| {
+            // pushed while applying
+            world.commands().push(cmd::Spawn { comp: U(2) });
+        });
+        cmds.push(cmd::Spawn { comp: U(1) });
+    }
+    world.run(sys);
+    assert_eq!(world.commands().len(), 3);
+
+    world.apply_commands();
+    assert!(world.commands().is_empty());
+    assert_eq!(world.comp::<U>().as_slice(), [U(0), U(1), U(2)]);
+}
+
+#[test]
+fn run_stage() {
+    use toecs::{app::Stage, cmd::CommandQueue, sys::erased::SystemResult};
+
+    let mut world = World::default();
+    world.register::<U>();
+    world.set_res(CommandQueue::default());
+    world.set_res(Vec::<usize>::new());
+
+    let mut stage = Stage::default();
+    stage
+        .add_system(|mut log: ResMut<Vec<usize>>, mut cmds: ResMut<CommandQueue>| {
+            log.push(0);
+            cmds.push(|world: &mut World| {
+                world.spawn(U(1));
+            });
+        })
+        .add_exclusive_system(|world: &mut World| {
+            // the command is applied
+            let n = world.comp::<U>().len();
+            world.res_mut::<Vec<usize>>().push(n);
+        })
+        .add_system(|mut log: ResMut<Vec<usize>>, us: Comp<U>| -> SystemResult {
+            log.push(us.as_slice()[0].0 + 1);
+            Ok(())
+        });
+
+    world.run_stage(&mut stage).unwrap();
+    assert_eq!(*world.res::<Vec<usize>>(), [0, 1, 2]);
+}
+
+#[test]
+fn run_stage_nested_commands() {
+    use toecs::{app::Stage, cmd::CommandQueue};
+
+    let mut world = World::default();
+    world.register::<U>();
+
+    let mut stage = Stage::default();
+    stage
+        .add_system(|mut cmds: ResMut<CommandQueue>| {
+            cmds.push(|world: &mut World| {
+                world.spawn(U(1));
+                // pushed while the queue is being applied
+                world.commands().push(|world: &mut World| {
+                    world.spawn(U(2));
+                });
+            });
+        })
+        .add_system(|us: Comp<U>| assert_eq!(us.as_slice(), [U(1), U(2)]));
+
+    world.commands();
+    world.run_stage(&mut stage).unwrap();
+    assert!(world.commands().is_empty());
+}
+
+#[test]
+fn run_seq() {
+    use toecs::sys::{erased::SystemResult, owned::IntoExclusiveBoxSystem};
+
+    let mut world = World::default();
+    world.set_res(Vec::<usize>::new());
+
+    let mut systems = [
+        (|mut log: ResMut<Vec<usize>>| log.push(0))
+            .into_ex_box_system()
+            .into_result_system(),
+        (|world: &mut World| -> SystemResult {
+            world.res_mut::<Vec<usize>>().push(1);
+            Err(anyhow::anyhow!("second system fails"))
+        })
+        .into_ex_box_system()
+        .into_result_system(),
+        (|mut log: ResMut<Vec<usize>>| log.push(2))
+            .into_ex_box_system()
+            .into_result_system(),
+    ];
+
+    assert!(world.run_seq(&mut systems).is_err());
+    assert_eq!(*world.res::<Vec<usize>>(), [0, 1]);
+}
+
+#[test]
+fn box_systems() {
+    use toecs::sys::{self, owned::BoxSystem};
+
+    fn read_u(u: Res<U>) -> usize {
+        u.0
+    }
+
+    fn count_i(i: Comp<I>) -> usize {
+        i.len()
+    }
+
+    fn sum(u: Res<U>, i: Comp<I>, f: Comp<F>) -> usize {
+        u.0 + i.len() + f.len()
+    }
+
+    let mut world = World::default();
+    world.set_res(U(10));
+    world.register_set::<(I, F)>();
+    world.spawn((I(0), F(0.0)));
+    world.spawn(I(1));
+
+    let mut systems: Vec<BoxSystem<usize>> =
+        vec![sys::into_box(read_u), sys::into_box(count_i), sys::into_box(sum)];
+
+    let rets = systems
+        .iter_mut()
+        .map(|sys| sys.run(&world))
+        .collect::<Vec<_>>();
+    assert_eq!(rets, [10, 2, 13]);
+
+    assert!(systems[0].name().ends_with("read_u"));
+    assert!(systems[2].name().ends_with("sum"));
+}
+
+#[test]
+fn app_run() {
+    use toecs::app::{App, AppExit};
+
+    const N: usize = 5;
+
+    let mut app = App::default();
+    app.insert_resource(I(0))
+        .add_startup_system(|mut i: ResMut<I>| i.0 += 100)
+        .add_system(|mut u: ResMut<U>| u.0 += 1)
+        .add_exclusive_system(|world: &mut World| {
+            if world.res::<U>().0 == N {
+                world.set_res(AppExit);
+            }
+        });
+
+    app.world_mut().set_res(U(0));
+    app.run().unwrap();
+
+    assert_eq!(*app.world().res::<U>(), U(N));
+    // startup systems run only once
+    assert_eq!(*app.world().res::<I>(), I(100));
+}
+
+#[test]
+fn take_res_set() {
+    let mut world = World::default();
+    world.set_res_set((U(10), I(-10)));
+
+    let taken = world.take_res_set::<(U, I, F)>();
+    assert_eq!(taken, (Some(U(10)), Some(I(-10)), None));
+    assert!(world.try_res::<U>().is_err());
+}
+
+#[test]
+fn iter_entities() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    let e0 = world.spawn((U(0), I(0)));
+    let e1 = world.spawn((U(1), I(1)));
+    let e2 = world.spawn(U(2));
+    let e3 = world.spawn((U(3), I(3)));
+
+    let (us, mut is) = (world.comp::<U>(), world.comp_mut::<I>());
+
+    // picked in arbitrary order
+    let picked = [e3, e1];
+    assert_eq!(
+        us.iter_entities(&picked).collect::<Vec<_>>(),
+        [(e3, &U(3)), (e1, &U(1))]
+    );
+
+    // `e2` doesn't have `I`
+    let picked = [e2, e0];
+    for (_ent, (u, i)) in (&us, &mut is).iter_entities(&picked) {
+        i.0 = -(u.0 as isize) - 10;
+    }
+    assert_eq!(is.get(e0), Some(&I(-10)));
+    assert_eq!(is.get(e1), Some(&I(1)));
+    drop((us, is));
+
+    // stale entities are skipped
+    world.despawn(e1);
+    let e4 = world.spawn(U(4));
+    assert_eq!(world.comp::<U>().iter_entities(&[e1, e4]).count(), 1);
+}
+
+#[test]
+#[should_panic]
+fn iter_entities_duplicate() {
+    let mut world = World::default();
+    world.register::<U>();
+
+    let e0 = world.spawn(U(0));
+    let mut us = world.comp_mut::<U>();
+    let _ = (&mut us).iter_entities(&[e0, e0]).count();
+}
+
+#[test]
+fn iter_entities_duplicate_read_only() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    let e0 = world.spawn((U(0), I(0)));
+    let us = world.comp::<U>();
+    assert_eq!(us.iter_entities(&[e0, e0]).count(), 2);
+
+    let is = world.comp::<I>();
+    assert_eq!((&us, &is).iter_entities(&[e0, e0]).count(), 2);
+}
+
+#[test]
+#[should_panic]
+fn iter_entities_duplicate_mixed() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    let e0 = world.spawn((U(0), I(0)));
+    let us = world.comp::<U>();
+    let mut is = world.comp_mut::<I>();
+    let _ = (&us, &mut is).iter_entities(&[e0, e0]).count();
+}
+
+#[test]
+fn comps() {
+    let mut world = World::default();
+    world.register_set::<(U, I, F)>();
+
+    world.spawn((U(0), I(0), F(0.0)));
+    world.spawn((U(1), I(1)));
+    world.spawn((U(2), I(2), F(2.0)));
+
+    let items = world
+        .comps::<(U, I, F)>()
+        .iter()
+        .map(|(u, i, f)| (*u, *i, *f))
+        .collect::<Vec<_>>();
+    assert_eq!(items, [(U(0), I(0), F(0.0)), (U(2), I(2), F(2.0))]);
+}
+
+#[test]
+fn snapshot_restore() {
+    let mut world = World::default();
+    world.register_clone::<U>();
+    world.register_clone::<I>();
+
+    let e0 = world.spawn((U(0), I(0)));
+    let e1 = world.spawn(U(1));
+    let snapshot = world.snapshot();
+
+    // mutate
+    world.comp_mut::<U>()[e0].0 = 100;
+    world.despawn(e1);
+    let e2 = world.spawn((U(2), I(2)));
+    world.register_clone::<F>();
+    world.insert(e0, F(0.0));
+
+    world.restore(snapshot);
+
+    assert_eq!(world.entities(), [e0, e1]);
+    assert!(!world.contains(e2));
+    assert_eq!(world.comp::<U>().get(e0), Some(&U(0)));
+    assert_eq!(world.comp::<U>().get(e1), Some(&U(1)));
+    assert_eq!(world.comp::<I>().get(e0), Some(&I(0)));
+    assert!(world.comp::<F>().is_empty());
+}
+
+#[test]
+fn snapshot_diff() {
+    use toecs::world::comp::PoolDiff;
+
+    let mut world = World::default();
+    world.register_diff::<U>();
+    world.register_diff::<I>();
+
+    let e0 = world.spawn((U(0), I(0)));
+    let e1 = world.spawn(U(1));
+    let e2 = world.spawn(U(2));
+    let s0 = world.snapshot();
+
+    // mutate two components
+    world.comp_mut::<U>()[e0].0 = 10;
+    world.insert(e1, I(1));
+    let s1 = world.snapshot();
+
+    let diff = s0.diff(&s1);
+    assert_eq!(diff.n_pools(), 2);
+    assert_eq!(
+        diff.get::<U>(),
+        Some(&PoolDiff {
+            inserted: vec![(e0, U(10))],
+            removed: vec![],
+        })
+    );
+    assert_eq!(
+        diff.get::<I>(),
+        Some(&PoolDiff {
+            inserted: vec![(e1, I(1))],
+            removed: vec![],
+        })
+    );
+
+    // roll back and forward
+    world.restore(s0);
+    assert_eq!(world.comp::<U>().get(e0), Some(&U(0)));
+    world.apply_diff(&diff);
+    assert_eq!(world.comp::<U>().get(e0), Some(&U(10)));
+    assert_eq!(world.comp::<I>().get(e1), Some(&I(1)));
+
+    // removal
+    let s1 = world.snapshot();
+    world.despawn(e2);
+    let diff = s1.diff(&world.snapshot());
+    assert_eq!(diff.get::<U>().unwrap().removed, [e2]);
+    assert!(diff.get::<I>().is_none());
+}
+
+#[test]
+fn snapshot_diff_hierarchy() {
+    let mut world = World::default();
+    world.register_diff::<U>();
+    world.register_hierarchy();
+
+    let parent = world.spawn(U(0));
+    let child = world.spawn(U(1));
+    let s0 = world.snapshot();
+
+    world.add_child(parent, child);
+    let s1 = world.snapshot();
+
+    let diff = s0.diff(&s1);
+    assert_eq!(
+        diff.get::<Parent>().unwrap().inserted,
+        [(child, Parent(parent))]
+    );
+    assert_eq!(
+        diff.get::<Children>().unwrap().inserted,
+        [(parent, Children(vec![child]))]
+    );
+    assert!(diff.get::<U>().is_none());
+
+    world.restore(s0);
+    world.apply_diff(&diff);
+    assert_eq!(world.comp::<Parent>().get(child), Some(&Parent(parent)));
+    assert_eq!(
+        world.comp::<Children>().get(parent),
+        Some(&Children(vec![child]))
+    );
+}
+
+#[test]
+#[should_panic]
+fn snapshot_not_clone() {
+    let mut world = World::default();
+    world.register::<U>();
+    let _ = world.snapshot();
+}
+
+#[test]
+fn duplicate() {
+    let mut world = World::default();
+    world.register_clone::<U>();
+    world.register_clone::<I>();
+
+    let e0 = world.spawn((U(0), I(0)));
+    let e1 = world.spawn(U(1));
+
+    let mut dup = world.duplicate().unwrap();
+    assert_eq!(dup.entities(), [e0, e1]);
+    assert_eq!(dup.comp::<U>().as_slice(), [U(0), U(1)]);
+
+    // mutate the duplicate independently
+    dup.comp_mut::<U>().get_mut(e0).unwrap().0 = 10;
+    dup.despawn(e1);
+    let e2 = dup.spawn(I(2));
+
+    assert_eq!(world.comp::<U>().as_slice(), [U(0), U(1)]);
+    assert!(world.contains(e1) && !world.contains(e2));
+    assert_eq!(dup.comp::<U>().as_slice(), [U(10)]);
+
+    // the clone support is kept
+    assert!(dup.duplicate().is_ok());
+
+    world.register::<F>();
+    let err = world.duplicate().unwrap_err();
+    assert_eq!(err, comp::CloneError(vec![std::any::type_name::<F>()]));
+}
+
+#[test]
+fn despawn_take() {
+    let mut world = World::default();
+    world.register_set::<(U, I)>();
+
+    let e0 = world.spawn((U(0), I(0)));
+    let e1 = world.spawn(U(1));
+
+    // missing component: nothing is removed
+    assert_eq!(world.despawn_take::<(U, I)>(e1), None);
+    assert!(world.contains(e1));
+    assert_eq!(world.comp::<U>().get(e1), Some(&U(1)));
+
+    let set = world.despawn_take::<(U, I)>(e0).unwrap();
+    assert_eq!(set, (U(0), I(0)));
+    assert!(!world.contains(e0));
+    assert_eq!(world.despawn_take::<(U, I)>(e0), None);
+
+    let e2 = world.spawn(set);
+    assert_eq!(world.comp::<U>().get(e2), Some(&U(0)));
+    assert_eq!(world.comp::<I>().get(e2), Some(&I(0)));
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+struct Link(Entity);
+
+impl MapEntities for Link {
+    fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+        self.0.map_entities(f);
+    }
+}
+
+#[test]
+fn map_entities() {
+    let mut world = World::default();
+    world.register_map_entities::<Link>();
+
+    let old = world.spawn_empty();
+    let other = world.spawn_empty();
+    let l1 = world.spawn(Link(old));
+    let l2 = world.spawn(Link(other));
+
+    // merge `old` into a new entity
+    let new = world.spawn_empty();
+    world.despawn(old);
+    world.map_entities(|e| if e == old { new } else { e });
+
+    assert_eq!(world.comp::<Link>().get(l1), Some(&Link(new)));
+    assert_eq!(world.comp::<Link>().get(l2), Some(&Link(other)));
+}
+
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+struct Links(Vec<Entity>);
+
+impl MapEntities for Links {
+    fn map_entities(&mut self, f: &mut dyn FnMut(Entity) -> Entity) {
+        self.0.iter_mut().for_each(|e| e.map_entities(f));
+    }
+}
+
+#[test]
+fn visit_components_of() {
+    let mut world = World::default();
+    world.register_map_entities::<Link>();
+    world.register_map_entities::<Links>();
+    world.register::<U>();
+
+    let old = world.spawn_empty();
+    let new = world.spawn_empty();
+    let e = world.spawn((Link(old), Links(vec![old, new]), U(0)));
+    let other = world.spawn(Link(old));
+
+    // `U` is not visited
+    let mut n_visits = 0;
+    world.visit_components_of(e, |comp| {
+        n_visits += 1;
+        comp.map_entities(&mut |e| if e == old { new } else { e });
+    });
+
+    assert_eq!(n_visits, 2);
+    assert_eq!(world.comp::<Link>().get(e), Some(&Link(new)));
+    assert_eq!(world.comp::<Links>().get(e), Some(&Links(vec![new, new])));
+    assert_eq!(world.comp::<Link>().get(other), Some(&Link(old)));
+}
+
+#[test]
+fn merge() {
+    use toecs::world::res::MergePolicy;
+
+    let mut world = World::default();
+    world.register::<U>();
+    world.register_map_entities::<Link>();
+    world.set_res_set((U(0), I(0)));
+    let e0 = world.spawn(U(0));
+    let e1 = world.spawn(Link(e0));
+
+    let mut other = World::default();
+    other.register::<U>();
+    other.register::<F>();
+    other.set_res_set((U(1), F(1.0)));
+    let dead = other.spawn_empty();
+    let o0 = other.spawn((U(10), F(10.0)));
+    let o1 = other.spawn_empty();
+    other.despawn(dead);
+    // `Link` is registered with `MapEntities` only in `world`
+    other.register::<Link>();
+    other.insert(o1, Link(o0));
+    let o2 = other.spawn(Link(dead));
+
+    let map = world.merge(other, MergePolicy::Keep);
+    assert_eq!(map.len(), 3);
+    assert_eq!(world.entities().len(), 5);
+
+    assert_eq!(world.comp::<U>().as_slice(), [U(0), U(10)]);
+    assert_eq!(world.comp::<F>().get(map[&o0]), Some(&F(10.0)));
+
+    // cross-references are remapped only for the merged components
+    assert_eq!(world.comp::<Link>().get(e1), Some(&Link(e0)));
+    assert_eq!(world.comp::<Link>().get(map[&o1]), Some(&Link(map[&o0])));
+    // dangling references are dropped
+    assert!(world.contains(map[&o2]));
+    assert_eq!(world.comp::<Link>().get(map[&o2]), None);
+
+    // resources
+    assert_eq!(*world.res::<U>(), U(0));
+    assert_eq!(*world.res::<F>(), F(1.0));
+
+    let mut other = World::default();
+    other.set_res(U(2));
+    world.merge(other, MergePolicy::Overwrite);
+    assert_eq!(*world.res::<U>(), U(2));
+}
+
+#[test]
+fn merge_hierarchy() {
+    use toecs::world::res::MergePolicy;
+
+    let mut world = World::default();
+    world.register_hierarchy();
+    let [p0, c0] = [(); 2].map(|_| world.spawn_empty());
+    world.add_child(p0, c0);
+
+    let mut other = World::default();
+    other.register_hierarchy();
+    let [dead, p, c] = [(); 3].map(|_| other.spawn_empty());
+    other.add_child(p, c);
+    let orphan = other.spawn(Parent(dead));
+    other.despawn(dead);
+
+    let map = world.merge(other, MergePolicy::Keep);
+
+    assert_eq!(world.comp::<Parent>().get(map[&c]), Some(&Parent(map[&p])));
+    assert_eq!(
+        world.comp::<Children>().get(map[&p]),
+        Some(&Children(vec![map[&c]]))
+    );
+    assert_eq!(world.ancestors(c0).collect::<Vec<_>>(), [p0]);
+
+    // the dangling parent is dropped
+    assert!(world.contains(map[&orphan]));
+    assert_eq!(world.comp::<Parent>().get(map[&orphan]), None);
+    assert_eq!(world.comp::<Parent>().len(), 2);
+}
+
+#[test]
+fn archetype_summary() {
+    use std::any::TypeId;
+
+    let mut world = World::default();
+    world.register_set::<(U, I, F)>();
+
+    world.spawn((U(0), I(0)));
+    world.spawn(F(0.0));
+    world.spawn((I(1), U(1)));
+    world.spawn((U(2), I(2)));
+
+    let mut ui = vec![TypeId::of::<U>(), TypeId::of::<I>()];
+    ui.sort();
+
+    assert_eq!(
+        world.archetype_summary(),
+        [(ui, 3), (vec![TypeId::of::<F>()], 1)]
+    );
+}
+
+#[test]
+fn dump_json() {
+    let mut world = World::default();
+    world.set_res(F(1.0));
+    world.register_set::<(U, I)>();
+    world.spawn((U(10), I(-10)));
+
+    let dump = world.dump_json();
+    assert!(dump.contains(std::any::type_name::<F>()));
+    assert!(dump.contains(std::any::type_name::<U>()));
+    assert!(dump.contains(std::any::type_name::<I>()));
+}
+
+#[test]
+fn dump_json_escape() {
+    /// Debug string with raw control characters
+    struct Text;
+
+    impl std::fmt::Debug for Text {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a\tb\rc\u{1}\"\\")
+        }
+    }
+
+    let mut world = World::default();
+    world.set_res(Text);
+
+    let dump = world.dump_json();
+    assert!(dump.contains(r#""a\tb\rc\u0001\"\\""#), "{}", dump);
+    assert!(!dump.chars().any(|c| c.is_control() && c != '\n'));
 }
 
 // #[test]
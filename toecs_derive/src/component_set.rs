@@ -1,6 +1,6 @@
 use proc_macro2::TokenStream as TokenStream2;
 use quote::*;
-use syn::*;
+use syn::{spanned::Spanned, *};
 
 pub fn impl_component_set(ast: DeriveInput) -> TokenStream2 {
     let ty_ident = &ast.ident;
@@ -13,18 +13,54 @@ pub fn impl_component_set(ast: DeriveInput) -> TokenStream2 {
         _ => panic!("#[derive(ComponentSet)] only supports `struct`"),
     };
 
-    let fields = match &data.fields {
-        Fields::Named(x) => x,
-        _ => panic!("#[derive(ComponentSet)] only supports named fields"),
+    // `self.field` or `self.0`
+    let all_accessors = match &data.fields {
+        Fields::Named(xs) => xs
+            .named
+            .iter()
+            .map(|f| f.ident.to_token_stream())
+            .collect::<Vec<_>>(),
+        Fields::Unnamed(xs) => (0..xs.unnamed.len())
+            .map(|i| Index::from(i).to_token_stream())
+            .collect::<Vec<_>>(),
+        Fields::Unit => panic!("#[derive(ComponentSet)] doesn't support unit structs"),
     };
 
-    let field_names = fields.named.iter().map(|f| f.ident.as_ref().unwrap());
+    let is_skipped = match data
+        .fields
+        .iter()
+        .map(self::is_skipped)
+        .collect::<Result<Vec<_>>>()
+    {
+        Ok(xs) => xs,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    // split fields into component sets and `#[component_set(skip)]` fields
+    let (fields, skipped): (Vec<_>, Vec<_>) = data
+        .fields
+        .iter()
+        .zip(all_accessors)
+        .zip(is_skipped)
+        .partition(|(_, skip)| !skip);
+
+    let field_accessors = fields.iter().map(|((_, a), _)| a).collect::<Vec<_>>();
+    let skipped_accessors = skipped.iter().map(|((_, a), _)| a);
 
-    let field_tys = fields.named.iter().map(|f| &f.ty);
+    // `Default` is required for skipped fields. The span points the error to the field type.
+    let skipped_defaults = skipped.iter().map(|((f, _), _)| {
+        let ty = &f.ty;
+        quote_spanned! { ty.span() => <#ty as ::core::default::Default>::default() }
+    });
+
+    let field_tys = fields.iter().map(|((f, _), _)| &f.ty).collect::<Vec<_>>();
     let tuple_ty = quote! {
         (#(#field_tys,)*)
     };
 
+    // `taken.0`, `taken.1`, ..
+    let taken_indices = (0..field_tys.len()).map(Index::from);
+
     quote! {
         impl #impl_generics ComponentSet for #ty_ident #ty_generics #where_clause {
             fn register(map: &mut ComponentPoolMap) {
@@ -33,7 +69,7 @@ pub fn impl_component_set(ast: DeriveInput) -> TokenStream2 {
 
             fn insert(self, ent: Entity, world: &mut World) {
                 #(
-                    world.insert_set(ent, self.#field_names);
+                    world.insert_set(ent, self.#field_accessors);
                 )*
             }
 
@@ -41,9 +77,58 @@ pub fn impl_component_set(ast: DeriveInput) -> TokenStream2 {
                 <#tuple_ty as ComponentSet>::remove(ent, world);
             }
 
+            fn take(ent: Entity, world: &mut World) -> Option<Self> {
+                let taken = (#(
+                    <#field_tys as ComponentSet>::take(ent, world),
+                )*);
+                Some(Self {
+                    #(
+                        #field_accessors: taken.#taken_indices?,
+                    )*
+                    #(
+                        #skipped_accessors: #skipped_defaults,
+                    )*
+                })
+            }
+
             fn type_ids() -> Box<[::core::any::TypeId]> {
                 <#tuple_ty as ComponentSet>::type_ids()
             }
         }
     }
 }
+
+/// Returns true if the field is marked with `#[component_set(skip)]`
+fn is_skipped(field: &Field) -> Result<bool> {
+    let mut skip = false;
+
+    for attr in field.attrs.iter() {
+        if !attr.path.is_ident("component_set") {
+            continue;
+        }
+
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(Error::new_spanned(
+                    meta,
+                    "expected `#[component_set(skip)]`",
+                ))
+            }
+        };
+
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => skip = true,
+                _ => {
+                    return Err(Error::new_spanned(
+                        nested,
+                        "unknown `component_set` attribute, expected `skip`",
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(skip)
+}
@@ -10,13 +10,24 @@ pub fn impl_auto_fetch(ast: DeriveInput) -> TokenStream2 {
         _ => panic!("#[derive(AutoFetchImpl)] is only for structs"),
     };
 
-    let fields = match &data.fields {
-        Fields::Named(xs) => xs,
-        _ => panic!("#[derive(AutoFetchImpl): only supports named fields"),
+    if let Err(err) = self::validate(&ast, &data.fields) {
+        return err.to_compile_error();
+    }
+
+    // `field` or `0` (`Type { 0: .. }` is valid for tuple structs)
+    let field_idents = match &data.fields {
+        Fields::Named(xs) => xs
+            .named
+            .iter()
+            .map(|f| f.ident.to_token_stream())
+            .collect::<Vec<_>>(),
+        Fields::Unnamed(xs) => (0..xs.unnamed.len())
+            .map(|i| Index::from(i).to_token_stream())
+            .collect::<Vec<_>>(),
+        Fields::Unit => panic!("#[derive(AutoFetchImpl)] doesn't support unit structs"),
     };
 
-    let field_tys = fields.named.iter().map(|f| &f.ty).collect::<Vec<_>>();
-    let field_idents = fields.named.iter().map(|f| &f.ident);
+    let field_tys = data.fields.iter().map(|f| &f.ty).collect::<Vec<_>>();
 
     let gat_hack = format_ident!("GatHack{}", ty_ident);
 
@@ -51,3 +62,60 @@ pub fn impl_auto_fetch(ast: DeriveInput) -> TokenStream2 {
         }
     }
 }
+
+/// Emits readable errors for what we can statically tell is wrong
+fn validate(ast: &DeriveInput, fields: &Fields) -> Result<()> {
+    let generics = &ast.generics;
+    if generics.params.len() != 1 || generics.lifetimes().count() != 1 {
+        let msg = "#[derive(AutoFetch)] requires exactly one lifetime parameter: `Type<'w>`";
+        return Err(if generics.params.is_empty() {
+            Error::new_spanned(&ast.ident, msg)
+        } else {
+            Error::new_spanned(generics, msg)
+        });
+    }
+
+    let mut errors = fields
+        .iter()
+        .filter(|f| !self::is_fetch_shape(&f.ty))
+        .map(|f| {
+            Error::new_spanned(
+                &f.ty,
+                "`AutoFetch` field has to borrow the world, e.g. `Res<'w, T>` or `Comp<'w, T>`",
+            )
+        });
+
+    match errors.next() {
+        Some(mut err) => {
+            errors.for_each(|e| err.combine(e));
+            Err(err)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Returns false if the type is known not to be an `AutoFetch` type
+fn is_fetch_shape(ty: &Type) -> bool {
+    match ty {
+        // `Res<'w, T>`, `Comp<'w, T>` or user-defined `CustomFetch<'w>`
+        Type::Path(ty) => ty
+            .path
+            .segments
+            .last()
+            .is_some_and(|seg| match &seg.arguments {
+                PathArguments::AngleBracketed(args) => args
+                    .args
+                    .iter()
+                    .any(|arg| matches!(arg, GenericArgument::Lifetime(_))),
+                _ => false,
+            }),
+        // `&'w EntityPool`
+        Type::Reference(ty) => ty.mutability.is_none(),
+        Type::Tuple(ty) => ty.elems.iter().all(self::is_fetch_shape),
+        Type::Paren(ty) => self::is_fetch_shape(&ty.elem),
+        Type::Group(ty) => self::is_fetch_shape(&ty.elem),
+        // we can't tell
+        Type::Macro(_) | Type::Verbatim(_) => true,
+        _ => false,
+    }
+}
@@ -17,6 +17,9 @@ pub fn component(input: TokenStream) -> TokenStream {
 /// Implements `ComponentSet` trait
 ///
 /// User has to import `Component`, `ComponentSet`, `Entity` and `ComponentPoolMap to use this macro
+///
+/// Fields marked with `#[component_set(skip)]` are ignored on register, insert and remove. They
+/// are set to `Default::default()` on take, so their types have to implement `Default`.
 #[proc_macro_derive(ComponentSet, attributes(component_set))]
 pub fn component_set(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
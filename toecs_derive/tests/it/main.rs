@@ -43,6 +43,31 @@ fn custom_fetch_access_set() {
     world.run(test_custom_fetch);
 }
 
+#[derive(AutoFetch)]
+pub struct TupleFetch<'w>(Res<'w, U>, Comp<'w, I>);
+
+#[test]
+fn tuple_fetch() {
+    let mut world = World::default();
+
+    world.register::<I>();
+    world.set_res(U(10));
+    world.spawn(I(20));
+
+    fn test_tuple_fetch(f: TupleFetch) -> u32 {
+        f.0 .0 + f.1.as_slice().iter().map(|i| i.0).sum::<u32>()
+    }
+
+    assert_eq!(world.run(test_tuple_fetch), 30);
+}
+
+#[test]
+fn auto_fetch_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/fetch_*.rs");
+    t.compile_fail("tests/ui/component_set_*.rs");
+}
+
 #[derive(ComponentSet)]
 pub struct CustomComponentSet {
     u: U,
@@ -61,3 +86,66 @@ fn custom_component_set_derive() {
     let i = world.fetch::<Comp<I>>();
     assert_eq!(i.as_slice().len(), 1);
 }
+
+#[derive(ComponentSet)]
+pub struct TupleComponentSet(U, I);
+
+#[derive(ComponentSet)]
+pub struct GenericComponentSet<T: Component>(T, U);
+
+#[derive(ComponentSet)]
+pub struct WhereComponentSet<T>
+where
+    T: Component,
+{
+    t: T,
+}
+
+#[derive(ComponentSet)]
+pub struct SkipComponentSet {
+    u: U,
+    #[component_set(skip)]
+    config: Vec<u32>,
+}
+
+#[test]
+fn skip_component_set_derive() {
+    let mut world = World::default();
+
+    world.register_set::<SkipComponentSet>();
+    assert_eq!(SkipComponentSet::type_ids().len(), 1);
+
+    let e = world.spawn(SkipComponentSet {
+        u: U(10),
+        config: vec![20],
+    });
+    assert_eq!(world.comp::<U>().get(e).map(|u| u.0), Some(10));
+
+    let taken = world.despawn_take::<SkipComponentSet>(e).unwrap();
+    assert_eq!((taken.u.0, taken.config), (10, vec![]));
+}
+
+#[test]
+fn tuple_and_generic_component_set_derive() {
+    let mut world = World::default();
+
+    world.register_set::<TupleComponentSet>();
+    let e1 = world.spawn(TupleComponentSet(U(10), I(20)));
+    assert_eq!(world.comp::<U>().get(e1).map(|u| u.0), Some(10));
+    assert_eq!(world.comp::<I>().get(e1).map(|i| i.0), Some(20));
+
+    world.remove_set::<TupleComponentSet>(e1);
+    assert!(world.comp::<U>().get(e1).is_none());
+    assert!(world.comp::<I>().get(e1).is_none());
+
+    #[derive(Debug, Component)]
+    struct X(u32);
+
+    world.register_set::<GenericComponentSet<X>>();
+    let e2 = world.spawn(GenericComponentSet(X(30), U(40)));
+    assert_eq!(world.comp::<X>().get(e2).map(|x| x.0), Some(30));
+    assert_eq!(world.comp::<U>().get(e2).map(|u| u.0), Some(40));
+
+    world.spawn(WhereComponentSet { t: X(50) });
+    assert_eq!(world.comp::<X>().as_slice().len(), 2);
+}
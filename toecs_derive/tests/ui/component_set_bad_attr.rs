@@ -0,0 +1,13 @@
+use toecs::world::{comp::Component, ComponentSet};
+
+#[derive(Debug, Component)]
+struct U(u32);
+
+#[derive(ComponentSet)]
+pub struct BadAttr {
+    u: U,
+    #[component_set(ignore)]
+    config: u32,
+}
+
+fn main() {}
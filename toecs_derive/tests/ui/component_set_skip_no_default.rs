@@ -0,0 +1,22 @@
+use toecs::{
+    world::{
+        comp::{Component, ComponentPoolMap},
+        ent::Entity,
+        ComponentSet,
+    },
+    World,
+};
+
+#[derive(Debug, Component)]
+struct U(u32);
+
+struct NoDefault;
+
+#[derive(ComponentSet)]
+pub struct SkipNoDefault {
+    u: U,
+    #[component_set(skip)]
+    config: NoDefault,
+}
+
+fn main() {}
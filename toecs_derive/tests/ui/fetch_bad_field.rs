@@ -0,0 +1,9 @@
+use toecs::world::{fetch::AutoFetch, res::Res};
+
+#[derive(AutoFetch)]
+pub struct BadField<'w> {
+    res: Res<'w, u32>,
+    value: u32,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use toecs::world::fetch::AutoFetch;
+
+#[derive(AutoFetch)]
+pub struct NoLifetime {
+    value: u32,
+}
+
+fn main() {}